@@ -0,0 +1,11 @@
+//! Puts `memory.x` on the linker search path so `link.x` from cortex-m-rt can
+//! include it, regardless of which directory cargo is invoked from.
+
+use std::{env, fs, path::PathBuf};
+
+fn main() {
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::write(out.join("memory.x"), include_bytes!("memory.x")).unwrap();
+    println!("cargo:rustc-link-search={}", out.display());
+    println!("cargo:rerun-if-changed=memory.x");
+}
@@ -0,0 +1,21 @@
+MEMORY {
+    BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
+    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100 - 16K
+    /* Settings A/B slots then the two odometer log sectors, one 4K erase
+       sector each. Kept out of FLASH so the linker can never place code
+       there. */
+    SETTINGS : ORIGIN = 0x10000000 + 2048K - 16K, LENGTH = 16K
+    RAM   : ORIGIN = 0x20000000, LENGTH = 256K
+}
+
+EXTERN(BOOT2_FIRMWARE)
+
+__settings_start = ORIGIN(SETTINGS);
+
+SECTIONS {
+    /* ### Boot loader */
+    .boot2 ORIGIN(BOOT2) :
+    {
+        KEEP(*(.boot2));
+    } > BOOT2
+} INSERT BEFORE .text;
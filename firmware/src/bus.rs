@@ -0,0 +1,132 @@
+//! The shared I2C0 bus, and getting it back after a brownout.
+//!
+//! A device that browns out mid-read can be left driving SDA low while it
+//! waits for clocks that never come. Resetting the RP2040 doesn't help, the
+//! device isn't reset with it. The fix is to take the pins back as GPIO,
+//! clock SCL until the device lets go of SDA, send a STOP and then bring
+//! the I2C block back up.
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+    i2c::{ErrorType, I2c, Operation},
+};
+use fugit::{HertzU32, RateExtU32};
+use rp2040_hal::{
+    gpio::{
+        bank0::{Gpio24, Gpio25},
+        FunctionI2C, Pin, PinState, PullUp,
+    },
+    i2c::Error,
+    pac::{self, RESETS},
+    timer::Timer,
+    I2C,
+};
+
+pub type Sda = Pin<Gpio24, FunctionI2C, PullUp>;
+pub type Scl = Pin<Gpio25, FunctionI2C, PullUp>;
+type I2c0 = I2C<pac::I2C0, (Sda, Scl)>;
+
+const SDA_MASK: u32 = 1 << 24;
+/// Half an SCL period at 100 kHz.
+const HALF_CLOCK_US: u32 = 5;
+/// Enough to finish any byte plus its ACK.
+const RECOVERY_CLOCKS: usize = 9;
+
+pub struct Bus {
+    /// Only `None` while [`Bus::recover`] has the pins
+    i2c: Option<I2c0>,
+    resets: RESETS,
+    system_clock: HertzU32,
+    timer: Timer,
+}
+
+impl Bus {
+    pub fn new(
+        block: pac::I2C0,
+        sda: Sda,
+        scl: Scl,
+        mut resets: RESETS,
+        system_clock: HertzU32,
+        timer: Timer,
+    ) -> Self {
+        let i2c = I2C::i2c0(block, sda, scl, 100.kHz(), &mut resets, system_clock);
+        Self {
+            i2c: Some(i2c),
+            resets,
+            system_clock,
+            timer,
+        }
+    }
+
+    /// Whether something is holding SDA low.
+    ///
+    /// The controller releases SDA between transactions, so a low level
+    /// here means a device is stuck partway through one.
+    pub fn is_stuck(&self) -> bool {
+        // Reading GPIO_IN is side-effect free whatever function the pin has
+        let levels = unsafe { (*pac::SIO::ptr()).gpio_in().read().bits() };
+        levels & SDA_MASK == 0
+    }
+
+    /// Clocks a stuck device free and rebuilds the I2C block.
+    ///
+    /// Returns whether SDA is high again afterwards.
+    pub fn recover(&mut self) -> bool {
+        let Some(i2c) = self.i2c.take() else {
+            return false;
+        };
+        let (block, (sda, scl)) = i2c.free(&mut self.resets);
+        let mut sda = sda.into_pull_up_input();
+        let mut scl = scl.into_push_pull_output_in_state(PinState::High);
+
+        for _ in 0..RECOVERY_CLOCKS {
+            if sda.is_high().unwrap_or(false) {
+                break;
+            }
+            scl.set_low().ok();
+            self.timer.delay_us(HALF_CLOCK_US);
+            scl.set_high().ok();
+            self.timer.delay_us(HALF_CLOCK_US);
+        }
+
+        // STOP: SDA rises while SCL is high. SDA is only ever driven low;
+        // releasing it lets the pull-up do the rising.
+        scl.set_low().ok();
+        self.timer.delay_us(HALF_CLOCK_US);
+        let sda = sda.into_push_pull_output_in_state(PinState::Low);
+        self.timer.delay_us(HALF_CLOCK_US);
+        scl.set_high().ok();
+        self.timer.delay_us(HALF_CLOCK_US);
+        let mut sda = sda.into_pull_up_input();
+        self.timer.delay_us(HALF_CLOCK_US);
+        let released = sda.is_high().unwrap_or(false);
+
+        self.i2c = Some(I2C::i2c0(
+            block,
+            sda.reconfigure(),
+            scl.reconfigure(),
+            100.kHz(),
+            &mut self.resets,
+            self.system_clock,
+        ));
+        released
+    }
+}
+
+impl ErrorType for Bus {
+    type Error = Error;
+}
+
+impl I2c for Bus {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        match self.i2c.as_mut() {
+            Some(i2c) => i2c.transaction(address, operations),
+            None => Err(Error::Abort(0)),
+        }
+    }
+}
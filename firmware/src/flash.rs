@@ -0,0 +1,188 @@
+//! Settings slots and the odometer log in on-board flash.
+//!
+//! `memory.x` reserves four 4K sectors after the program as `SETTINGS`:
+//! settings slots A and B, then odometer log sectors A and B. Reading is
+//! plain XIP. Writing has to run from RAM with interrupts off, since the
+//! flash can't be read while it's being erased or programmed.
+//!
+//! Afterwards XIP is set up again by a RAM copy of boot2, the same second
+//! stage that set it up at power-on, so reads stay in its fast quad mode.
+//! [`init`] takes that copy and has to run before the first write.
+
+use core::{
+    ptr::{addr_of, addr_of_mut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use hardbody_core::{
+    Odometer, OdometerLog, Settings, SettingsStore, Slot, ODOMETER_RECORD_LEN, RECORD_LEN,
+};
+use rp2040_hal::rom_data;
+
+/// Erase granularity of the QSPI flash.
+const SECTOR: usize = 4096;
+/// Program granularity.
+const PAGE: usize = 256;
+/// A record rounded up to whole pages.
+const PROGRAM_LEN: usize = RECORD_LEN.next_multiple_of(PAGE);
+/// Where XIP maps the start of flash.
+const XIP_BASE: usize = 0x1000_0000;
+/// Sector erase command for `flash_range_erase`.
+const SECTOR_ERASE: u8 = 0x20;
+/// boot2 fills the first 256 bytes of flash, CRC included.
+const BOOT2_WORDS: usize = 256 / 4;
+
+/// boot2, copied out of flash by [`init`]. It can't be run from flash
+/// while XIP is down.
+static mut BOOT2: [u32; BOOT2_WORDS] = [0; BOOT2_WORDS];
+static BOOT2_COPIED: AtomicBool = AtomicBool::new(false);
+
+extern "C" {
+    /// Defined in `memory.x`.
+    static __settings_start: u8;
+}
+
+#[derive(Debug)]
+pub struct VerifyFailed;
+
+fn slot_addr(slot: Slot) -> usize {
+    let start = unsafe { addr_of!(__settings_start) } as usize;
+    start + slot as usize * SECTOR
+}
+
+fn slot_bytes(slot: Slot) -> &'static [u8] {
+    unsafe { core::slice::from_raw_parts(slot_addr(slot) as *const u8, RECORD_LEN) }
+}
+
+fn odometer_addr(slot: Slot) -> usize {
+    slot_addr(Slot::A) + (2 + slot as usize) * SECTOR
+}
+
+fn odometer_bytes(slot: Slot) -> &'static [u8] {
+    unsafe { core::slice::from_raw_parts(odometer_addr(slot) as *const u8, SECTOR) }
+}
+
+/// Copies boot2 to RAM. Call once at startup.
+pub fn init() {
+    let boot2 = XIP_BASE as *const u32;
+    unsafe {
+        for (i, word) in (*addr_of_mut!(BOOT2)).iter_mut().enumerate() {
+            *word = boot2.add(i).read_volatile();
+        }
+    }
+    BOOT2_COPIED.store(true, Ordering::Release);
+}
+
+/// Newest good settings from flash, or the compiled defaults.
+pub fn load() -> (SettingsStore, Settings) {
+    SettingsStore::load(slot_bytes(Slot::A), slot_bytes(Slot::B))
+}
+
+/// Writes `settings` to the older slot and reads it back.
+///
+/// The store only moves on to the new slot once the read-back decodes, so
+/// a failed write leaves the previous record as the one in use.
+pub fn save(store: &mut SettingsStore, settings: &Settings) -> Result<(), VerifyFailed> {
+    let pending = store.prepare(settings);
+    let mut page = [0xFF; PROGRAM_LEN];
+    page[..RECORD_LEN].copy_from_slice(&pending.record);
+
+    let offset = (slot_addr(pending.slot) - XIP_BASE) as u32;
+    let rom = Rom::lookup();
+    critical_section::with(|_| unsafe { program(&rom, offset, true, &page) });
+
+    match Settings::decode(slot_bytes(pending.slot)) {
+        Some((sequence, written)) if sequence == pending.sequence && written == *settings => {
+            store.commit(&pending);
+            Ok(())
+        }
+        _ => Err(VerifyFailed),
+    }
+}
+
+/// Newest saved odometer, or zero on a fresh chip.
+pub fn load_odometer() -> (OdometerLog, Odometer) {
+    OdometerLog::load(odometer_bytes(Slot::A), odometer_bytes(Slot::B))
+}
+
+/// Appends `odometer` to the log and reads it back.
+///
+/// Only the page holding the new record is programmed, with every other
+/// byte left at 0xFF so the records already there keep their bits. On a
+/// failed read-back the log is rescanned, which steps past the bad record.
+pub fn save_odometer(log: &mut OdometerLog, odometer: &Odometer) -> Result<(), VerifyFailed> {
+    let pending = log.prepare(odometer);
+    let in_page = pending.offset % PAGE;
+    let mut page = [0xFF; PAGE];
+    page[in_page..in_page + ODOMETER_RECORD_LEN].copy_from_slice(&pending.record);
+
+    let sector = odometer_addr(pending.slot);
+    let offset = (sector + pending.offset - in_page - XIP_BASE) as u32;
+    let rom = Rom::lookup();
+    critical_section::with(|_| unsafe { program(&rom, offset, pending.erase, &page) });
+
+    let written = &odometer_bytes(pending.slot)[pending.offset..];
+    match Odometer::decode(written) {
+        Some((sequence, read)) if sequence == pending.sequence && read == *odometer => {
+            log.commit(&pending);
+            Ok(())
+        }
+        _ => {
+            *log = load_odometer().0;
+            Err(VerifyFailed)
+        }
+    }
+}
+
+/// Boot ROM flash routines, looked up while XIP still works.
+struct Rom {
+    connect_internal_flash: unsafe extern "C" fn(),
+    flash_exit_xip: unsafe extern "C" fn(),
+    flash_range_erase: unsafe extern "C" fn(u32, usize, u32, u8),
+    flash_range_program: unsafe extern "C" fn(u32, *const u8, usize),
+    flash_flush_cache: unsafe extern "C" fn(),
+    /// boot2's RAM copy, or the ROM's slow 03h XIP setup if [`init`]
+    /// never ran
+    enter_xip: unsafe extern "C" fn(),
+}
+
+impl Rom {
+    fn lookup() -> Self {
+        let enter_xip = match BOOT2_COPIED.load(Ordering::Acquire) {
+            // boot2 is Thumb code starting at its first byte; a call
+            // needs the low bit set. It returns to the caller when entered
+            // with a return address, as it is here.
+            true => unsafe {
+                let entry = addr_of!(BOOT2) as usize | 1;
+                core::mem::transmute::<usize, unsafe extern "C" fn()>(entry)
+            },
+            false => rom_data::flash_enter_cmd_xip::ptr(),
+        };
+        Self {
+            connect_internal_flash: rom_data::connect_internal_flash::ptr(),
+            flash_exit_xip: rom_data::flash_exit_xip::ptr(),
+            flash_range_erase: rom_data::flash_range_erase::ptr(),
+            flash_range_program: rom_data::flash_range_program::ptr(),
+            flash_flush_cache: rom_data::flash_flush_cache::ptr(),
+            enter_xip,
+        }
+    }
+}
+
+/// Programs whole pages at `offset`, erasing the sector there first if
+/// asked.
+///
+/// Runs from RAM (`.data` is copied there at boot); touches nothing in
+/// flash between leaving and re-entering XIP.
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn program(rom: &Rom, offset: u32, erase: bool, data: &[u8]) {
+    (rom.connect_internal_flash)();
+    (rom.flash_exit_xip)();
+    if erase {
+        (rom.flash_range_erase)(offset, SECTOR, SECTOR as u32, SECTOR_ERASE);
+    }
+    (rom.flash_range_program)(offset, data.as_ptr(), data.len());
+    (rom.flash_flush_cache)();
+    (rom.enter_xip)();
+}
@@ -0,0 +1,646 @@
+#![no_std]
+#![no_main]
+
+mod bus;
+mod flash;
+mod panic;
+mod pulse;
+mod reset;
+mod usb;
+
+use adafruit_qt_py_rp2040::entry;
+use adafruit_qt_py_rp2040::{hal, Pins, XOSC_CRYSTAL_FREQ};
+use ads1x1x::{
+    channel,
+    ic::{Ads1115, Resolution16Bit},
+    mode::OneShot,
+    Ads1x1x, DataRate16Bit, FullScaleRange, TargetAddr,
+};
+
+use hardbody_core::{
+    console, draw_boot_screen, draw_crash_report, draw_fault, draw_page, draw_warning, Acquisition,
+    Action, Alert, Alerts, ClusterError, Context, CrashReport, Device, FaultLog, FilterConfig,
+    FixedConversions, FuelReading, GaugeConfig, Input, InputConfig, MinMax, OilPressureReading,
+    Operation, Page, Pager, RailState, RawCodes, Recovery, ReferenceReading, ResetLog, SloshFilter,
+    Snapshot, WarningEngine, WarningInputs,
+};
+
+use core::{cell::RefCell, fmt::Write};
+use embedded_hal_bus::i2c;
+
+use bus::Bus;
+use embedded_hal::{delay::DelayNs, digital::InputPin};
+use fugit::{MicrosDurationU32, MicrosDurationU64, RateExtU32};
+use hal::{
+    clocks::init_clocks_and_plls,
+    gpio::{DynPinId, FunctionSioInput, Pin, PullUp},
+    pac,
+    timer::Timer,
+    watchdog::Watchdog,
+    Sio,
+};
+use ssd1306::{mode::BufferedGraphicsMode, prelude::*, I2CDisplayInterface, Ssd1306};
+
+/// How long each page shows on a panel that rotates.
+const PAGE_DWELL_MS: u32 = 5_000;
+/// Below cranking speed the engine counts as stopped.
+const ENGINE_RUNNING_RPM: f32 = 400.0;
+/// How long the reset report stays up at boot.
+const BOOT_SCREEN_MS: u32 = 1_500;
+/// Longer when there's a panic message to read.
+const CRASH_SCREEN_MS: u32 = 5_000;
+/// A loop normally takes a few hundred ms; a flash save adds a sector erase.
+const WATCHDOG_TIMEOUT: MicrosDurationU32 = MicrosDurationU32::millis(2_000);
+
+type BusDevice<'a> = i2c::RefCellDevice<'a, Bus>;
+type Display<'a> = Ssd1306<
+    I2CInterface<BusDevice<'a>>,
+    DisplaySize128x64,
+    BufferedGraphicsMode<DisplaySize128x64>,
+>;
+type Adc<'a> = Ads1x1x<BusDevice<'a>, Ads1115, Resolution16Bit, OneShot>;
+/// An ADS1115's ALERT/RDY output, open-drain and low once a conversion is
+/// done.
+type ReadyPin = Pin<DynPinId, FunctionSioInput, PullUp>;
+
+const ADCS: [Device; 2] = [Device::Adc1, Device::Adc2];
+const DISPLAYS: [Device; 2] = [Device::Display1, Device::Display2];
+
+/// One conversion at 128 SPS, rounded up.
+const CONVERSION_MS: u32 = 8;
+
+/// ADC1: coolant, fuel and battery, with the 3.3V rail on A3. The senders
+/// and the rail are low impedance; only the 100k/22k battery divider is
+/// too high an impedance to recharge the ADC within one conversion, so it
+/// throws one away after the mux moves. The senders get a
+/// median of three against ignition spikes; the rail moves slowly and just
+/// gets an average.
+const ADC1_INPUTS: [InputConfig; 4] = [
+    InputConfig {
+        input: Input::A0,
+        period_ms: 500,
+        settle: 0,
+        filter: SPIKES,
+    },
+    InputConfig {
+        input: Input::A1,
+        period_ms: 200,
+        settle: 0,
+        filter: SPIKES,
+    },
+    InputConfig {
+        input: Input::A2,
+        period_ms: 500,
+        settle: 1,
+        filter: SPIKES,
+    },
+    InputConfig {
+        input: Input::A3,
+        period_ms: 200,
+        settle: 0,
+        filter: RAIL,
+    },
+];
+/// ADC2: oil pressure, with the 3.3V rail on A3. Oil pressure is sampled
+/// fast enough to smooth a little without lagging.
+const ADC2_INPUTS: [InputConfig; 2] = [
+    InputConfig {
+        input: Input::A0,
+        period_ms: 100,
+        settle: 0,
+        filter: FilterConfig {
+            ema_shift: 1,
+            ..SPIKES
+        },
+    },
+    InputConfig {
+        input: Input::A3,
+        period_ms: 200,
+        settle: 0,
+        filter: RAIL,
+    },
+];
+const SPIKES: FilterConfig = FilterConfig {
+    median: 3,
+    oversample: 1,
+    ema_shift: 0,
+};
+const RAIL: FilterConfig = FilterConfig {
+    median: 1,
+    oversample: 1,
+    ema_shift: 2,
+};
+
+/// Starts a conversion on `input`, or collects it once it's done.
+macro_rules! read {
+    ($adc:expr, $input:expr) => {
+        match $input {
+            Input::A0 => $adc.read(channel::SingleA0),
+            Input::A1 => $adc.read(channel::SingleA1),
+            Input::A2 => $adc.read(channel::SingleA2),
+            Input::A3 => $adc.read(channel::SingleA3),
+        }
+    };
+}
+
+/// Moves one ADC's conversions along without waiting on them: collects a
+/// finished one, then starts whatever is due next.
+macro_rules! acquire {
+    ($adc:expr, $acquisition:expr, $ready:expr, $device:expr, $now_ms:expr) => {{
+        let error = ClusterError::new($device, Operation::Read);
+        if $acquisition.timed_out($now_ms) {
+            $acquisition.reset();
+            return Err(error);
+        }
+        if let Some(input) = $acquisition.converting() {
+            let ready = $ready.is_low().unwrap_or(false);
+            if $acquisition.should_read($now_ms, ready) {
+                match read!($adc, input) {
+                    Ok(code) => {
+                        $acquisition.finish($now_ms, code);
+                    }
+                    // Not done after all; the timeout catches one that
+                    // never will be
+                    Err(nb::Error::WouldBlock) => {}
+                    Err(nb::Error::Other(_)) => {
+                        $acquisition.reset();
+                        return Err(error);
+                    }
+                }
+            }
+        }
+        if let Some(input) = $acquisition.start($now_ms) {
+            match read!($adc, input) {
+                // Started. `Ok` is a result left over from before a re-init;
+                // the next read starts a fresh conversion instead.
+                Err(nb::Error::WouldBlock) | Ok(_) => {}
+                Err(nb::Error::Other(_)) => {
+                    $acquisition.reset();
+                    return Err(error);
+                }
+            }
+        }
+    }};
+}
+
+/// The latest ADC codes. Fields from a missing ADC keep their last value.
+#[derive(Default)]
+struct Codes {
+    temp: i16,
+    fuel: i16,
+    battery: i16,
+    reference1: i16,
+    oil: i16,
+    reference2: i16,
+}
+
+/// Everything on the I2C bus.
+struct Devices<'a> {
+    bus: &'a RefCell<Bus>,
+    adcs: [Adc<'a>; 2],
+    ready: [ReadyPin; 2],
+    acquisition1: Acquisition<4>,
+    acquisition2: Acquisition<2>,
+    displays: [Display<'a>; 2],
+    /// Whether each panel is currently inverted for a flashing alert
+    inverted: [bool; 2],
+}
+
+impl Devices<'_> {
+    /// Sets up `device` from scratch.
+    fn reinit(&mut self, device: Device) -> Result<(), ClusterError> {
+        match device {
+            Device::Adc1 | Device::Adc2 => {
+                match device {
+                    Device::Adc1 => self.acquisition1.reset(),
+                    _ => self.acquisition2.reset(),
+                }
+                let adc = &mut self.adcs[device as usize - Device::Adc1 as usize];
+                let err = |_| ClusterError::new(device, Operation::Configure);
+                adc.set_data_rate(DataRate16Bit::Sps128).map_err(err)?;
+                adc.set_full_scale_range(FullScaleRange::Within4_096V)
+                    .map_err(err)?;
+                adc.use_alert_rdy_pin_as_ready().map_err(err)
+            }
+            Device::Display1 | Device::Display2 => {
+                let panel = device as usize - Device::Display1 as usize;
+                let display = &mut self.displays[panel];
+                let err = |_| ClusterError::new(device, Operation::Init);
+                display.init().map_err(err)?;
+                self.inverted[panel] = false;
+                display.clear_buffer();
+                display.flush().map_err(err)
+            }
+        }
+    }
+
+    /// Gives one ADC's conversions a nudge and copies out its newest
+    /// filtered readings. Never waits on a conversion.
+    fn acquire(
+        &mut self,
+        device: Device,
+        now_ms: u32,
+        codes: &mut Codes,
+    ) -> Result<(), ClusterError> {
+        let [adc, adc2] = &mut self.adcs;
+        let [ready, ready2] = &mut self.ready;
+        match device {
+            Device::Adc1 => {
+                acquire!(adc, self.acquisition1, ready, device, now_ms);
+                let [temp, fuel, battery, reference1] = self.acquisition1.latest();
+                *codes = Codes {
+                    temp: temp.unwrap_or(codes.temp),
+                    fuel: fuel.unwrap_or(codes.fuel),
+                    battery: battery.unwrap_or(codes.battery),
+                    reference1: reference1.unwrap_or(codes.reference1),
+                    ..*codes
+                };
+            }
+            Device::Adc2 => {
+                acquire!(adc2, self.acquisition2, ready2, device, now_ms);
+                let [oil, reference2] = self.acquisition2.latest();
+                *codes = Codes {
+                    oil: oil.unwrap_or(codes.oil),
+                    reference2: reference2.unwrap_or(codes.reference2),
+                    ..*codes
+                };
+            }
+            Device::Display1 | Device::Display2 => {}
+        }
+        Ok(())
+    }
+
+    /// The newest conversions, before filtering.
+    fn raw_codes(&self) -> RawCodes {
+        let [oil, reference2] = self.acquisition2.raw();
+        RawCodes {
+            adc1: self.acquisition1.raw(),
+            adc2: [oil, None, None, reference2],
+        }
+    }
+
+    /// [`Devices::acquire`] on every ADC that's present.
+    fn acquire_all(&mut self, faults: &mut FaultLog, now_ms: u32, codes: &mut Codes) {
+        for device in ADCS {
+            if faults.status().is_present(device) {
+                match self.acquire(device, now_ms, codes) {
+                    Ok(()) => faults.cleared(device),
+                    Err(e) => self.recover(faults, e),
+                }
+            }
+        }
+    }
+
+    /// Draws `page` on a panel, flashing it if there's an alert. Without
+    /// one, a missing device is shown in the alert's place.
+    #[allow(clippy::too_many_arguments)]
+    fn show(
+        &mut self,
+        panel: usize,
+        page: Page,
+        alert: Option<Alert>,
+        fault: Option<Device>,
+        flash_on: bool,
+        snapshot: &Snapshot,
+        gauges: &GaugeConfig,
+    ) -> Result<(), ClusterError> {
+        let device = DISPLAYS[panel];
+        let display = &mut self.displays[panel];
+        let draw = |_| ClusterError::new(device, Operation::Draw);
+        display.clear_buffer();
+        draw_page(display, page, snapshot, gauges).map_err(draw)?;
+        if let Some(alert) = alert {
+            draw_warning(display, alert).map_err(draw)?;
+        } else if let Some(missing) = fault {
+            draw_fault(display, missing).map_err(draw)?;
+        }
+        let invert = alert.is_some() && flash_on;
+        if self.inverted[panel] != invert {
+            display
+                .set_invert(invert)
+                .map_err(|_| ClusterError::new(device, Operation::Invert))?;
+            self.inverted[panel] = invert;
+        }
+        display
+            .flush()
+            .map_err(|_| ClusterError::new(device, Operation::Flush))
+    }
+
+    /// Logs a failure and does whatever the log says it deserves.
+    fn recover(&mut self, faults: &mut FaultLog, error: ClusterError) {
+        let stuck = self.bus.borrow().is_stuck();
+        match faults.record(error, stuck) {
+            Recovery::Retry => {}
+            // A failed re-init shows up on the next attempt to use the device
+            Recovery::Reinit => {
+                self.reinit(error.device).ok();
+            }
+            Recovery::RecoverBus => {
+                self.bus.borrow_mut().recover();
+                for device in Device::ALL {
+                    self.reinit(device).ok();
+                }
+            }
+            // Left out of the loop until `retry_missing` brings it back
+            Recovery::Offline => {}
+            Recovery::Reset => reset::software_reset(),
+        }
+    }
+
+    /// Puts the reset report on both panels, or on the first with the
+    /// panic message on the second.
+    fn show_boot_screen(&mut self, resets: &ResetLog, crash: Option<&CrashReport>) {
+        for (panel, display) in self.displays.iter_mut().enumerate() {
+            display.clear_buffer();
+            let drawn = match crash {
+                Some(crash) if panel == 1 => draw_crash_report(display, crash.as_str()),
+                _ => draw_boot_screen(display, resets),
+            };
+            // A panel that fails here is caught by the first real frame
+            if drawn.is_ok() {
+                display.flush().ok();
+            }
+        }
+    }
+
+    /// Tries the missing devices again, now and then.
+    fn retry_missing(&mut self, faults: &mut FaultLog, dt_ms: u32) {
+        if !faults.retry_due(dt_ms) {
+            return;
+        }
+        for device in faults.status().missing() {
+            if self.reinit(device).is_ok() {
+                faults.cleared(device);
+            }
+        }
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut watchdog = Watchdog::new(pac.WATCHDOG);
+    let resets = reset::on_boot(&mut watchdog, &pac.VREG_AND_CHIP_RESET);
+    let crash = panic::take();
+    let sio = Sio::new(pac.SIO);
+    let pins = Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    let clocks = init_clocks_and_plls(
+        XOSC_CRYSTAL_FREQ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+    usb::start(
+        pac.USBCTRL_REGS,
+        pac.USBCTRL_DPRAM,
+        clocks.usb_clock,
+        &mut pac.RESETS,
+    );
+    let timer = Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+    let i2c = Bus::new(
+        pac.I2C0,
+        pins.sda.reconfigure(),
+        pins.scl.reconfigure(),
+        pac.RESETS,
+        125_000_000.Hz(),
+        timer,
+    );
+
+    let i2c_ref_cell = RefCell::new(i2c);
+
+    let interface1 = I2CDisplayInterface::new(i2c::RefCellDevice::new(&i2c_ref_cell));
+    let interface2 =
+        I2CDisplayInterface::new_alternate_address(i2c::RefCellDevice::new(&i2c_ref_cell));
+    let mut devices = Devices {
+        bus: &i2c_ref_cell,
+        adcs: [
+            Ads1x1x::new_ads1115(i2c::RefCellDevice::new(&i2c_ref_cell), TargetAddr::Gnd),
+            // Second ADS1115 (ADDR → VDD): oil pressure sender on A0, 3.3V on A3
+            Ads1x1x::new_ads1115(i2c::RefCellDevice::new(&i2c_ref_cell), TargetAddr::Vdd),
+        ],
+        // ALERT/RDY of each ADC, on the A0 and A1 pads
+        ready: [
+            pins.a0.into_pull_up_input().into_dyn_pin(),
+            pins.a1.into_pull_up_input().into_dyn_pin(),
+        ],
+        acquisition1: Acquisition::new(ADC1_INPUTS, CONVERSION_MS),
+        acquisition2: Acquisition::new(ADC2_INPUTS, CONVERSION_MS),
+        displays: [
+            Ssd1306::new(interface1, DisplaySize128x64, DisplayRotation::Rotate0)
+                .into_buffered_graphics_mode(),
+            Ssd1306::new(interface2, DisplaySize128x64, DisplayRotation::Rotate0)
+                .into_buffered_graphics_mode(),
+        ],
+        inverted: [false; 2],
+    };
+    let mut faults = FaultLog::new();
+    for device in Device::ALL {
+        if let Err(e) = devices.reinit(device) {
+            devices.recover(&mut faults, e);
+        }
+    }
+    devices.show_boot_screen(&resets, crash.as_ref());
+    let mut delay = timer;
+    delay.delay_ms(match crash {
+        Some(_) => CRASH_SCREEN_MS,
+        None => BOOT_SCREEN_MS,
+    });
+
+    flash::init();
+    let (mut settings_store, mut settings) = flash::load();
+    pulse::start(
+        pins.tx.into_pull_up_input(),
+        pins.rx.into_pull_up_input(),
+        timer,
+        settings.tach,
+        settings.speed,
+    );
+
+    let mut fixed = FixedConversions::new(&settings);
+    let mut slosh = SloshFilter::new(settings.slosh);
+    let mut warnings = WarningEngine::new(settings.warnings);
+    let mut pagers = [
+        Pager::new(
+            &[Page::Coolant, Page::OilPressure, Page::Trip],
+            PAGE_DWELL_MS,
+        ),
+        Pager::new(&[Page::Speed, Page::Tach, Page::Fuel], PAGE_DWELL_MS),
+    ];
+    let now_ms = || (timer.get_counter().ticks() / 1_000) as u32;
+    let (mut odometer_log, mut odometer) = flash::load_odometer();
+    let mut odometer_saved_at = now_ms();
+    let mut last_frame = timer.get_counter();
+
+    let mut codes = Codes::default();
+    let mut coolant_range: Option<MinMax> = None;
+    watchdog.pause_on_debug(true);
+    watchdog.start(WATCHDOG_TIMEOUT);
+
+    loop {
+        let now = timer.get_counter();
+        let dt_ms = (now - last_frame).to_millis() as u32;
+        // Keep the part of a millisecond not counted yet, or a fast loop
+        // (no panels to flush) would see 0 ms every pass and stop time
+        last_frame += MicrosDurationU64::millis(dt_ms as u64);
+
+        devices.retry_missing(&mut faults, dt_ms);
+        devices.acquire_all(&mut faults, now_ms(), &mut codes);
+        let status = faults.status();
+
+        let coolant = fixed.coolant(codes.temp, codes.reference1);
+        if coolant.state.is_ok() {
+            match coolant_range.as_mut() {
+                Some(range) => range.update(coolant.fahrenheit),
+                None => coolant_range = Some(MinMax::new(coolant.fahrenheit)),
+            }
+        }
+        let reference = ReferenceReading::from_code(codes.reference1);
+        let reference2 = ReferenceReading::from_code(codes.reference2);
+        let fuel = fixed.fuel(codes.fuel, codes.reference1);
+        // A broken sender reads empty or full; keep it out of the filter
+        let level = match fuel.state.is_ok() {
+            true => Some(slosh.update(fuel.percent as f32, dt_ms)),
+            false => slosh.hold(),
+        };
+        let level = level.unwrap_or(fuel.percent as f32);
+        // The tolerance band moves with the damped level
+        let shift = level - fuel.percent as f32;
+        let fuel = FuelReading {
+            percent: (level + 0.5) as u8,
+            bounds: MinMax {
+                min: (fuel.bounds.min + shift).clamp(0.0, 100.0),
+                max: (fuel.bounds.max + shift).clamp(0.0, 100.0),
+            },
+            ..fuel
+        };
+        let battery = fixed.battery(codes.battery);
+        let oil = OilPressureReading::from_codes(
+            codes.oil,
+            codes.reference2,
+            &settings.oil_curve,
+            &settings.circuit,
+        );
+        let pulses = pulse::read();
+        let tach = pulses.tach;
+        odometer.add_pulses(pulses.vss_pulses, settings.speed.pulses_per_mile);
+        // Key-off cuts the power, so save as soon as the truck stops or
+        // the rail starts to sag. A failed save is retried after the next
+        // interval.
+        let urgent = pulses.speed.mph <= 0.0
+            || reference.state == RailState::Low
+            || reference2.state == RailState::Low;
+        let since_save_ms = now_ms().wrapping_sub(odometer_saved_at);
+        if odometer_log.due(&odometer, since_save_ms, urgent) {
+            flash::save_odometer(&mut odometer_log, &odometer).ok();
+            odometer_saved_at = now_ms();
+        }
+        let snapshot = Snapshot {
+            coolant,
+            coolant_range,
+            reference,
+            fuel,
+            battery,
+            oil,
+            tach,
+            speed: pulses.speed,
+            odometer,
+        };
+
+        if let Some(line) = usb::take_line() {
+            let raw = devices.raw_codes();
+            let mut ctx = Context {
+                settings: &mut settings,
+                snapshot: &snapshot,
+                raw: &raw,
+                faults: &faults,
+                resets: &resets,
+                crash: crash.as_ref().map(CrashReport::as_str),
+            };
+            let mut reply = usb::Reply;
+            match console::run(line.as_str(), &mut ctx, &mut reply) {
+                Ok(Action::SettingsChanged) => {
+                    fixed = FixedConversions::new(&settings);
+                    slosh = SloshFilter::new(settings.slosh);
+                    warnings = WarningEngine::new(settings.warnings);
+                    pulse::configure(settings.tach, settings.speed);
+                }
+                Ok(Action::Save) => {
+                    let result = match flash::save(&mut settings_store, &settings) {
+                        Ok(()) => "saved\r\n",
+                        Err(_) => "save failed\r\n",
+                    };
+                    reply.write_str(result).ok();
+                }
+                Ok(Action::Reboot) => reset::software_reset(),
+                Ok(Action::ResetTrip(trip)) => {
+                    odometer.reset_trip(trip);
+                    flash::save_odometer(&mut odometer_log, &odometer).ok();
+                    odometer_saved_at = now_ms();
+                }
+                Ok(Action::None) | Err(_) => {}
+            }
+        }
+
+        // Stale readings from a missing ADC mustn't raise or clear alerts
+        let alerts = if ADCS.iter().all(|&d| status.is_present(d)) {
+            warnings.update(
+                &WarningInputs {
+                    coolant_f: coolant.fahrenheit,
+                    battery_v: battery.volts,
+                    fuel_pct: fuel.percent as f32,
+                    oil_psi: oil.psi,
+                    engine_running: tach.rpm >= ENGINE_RUNNING_RPM,
+                    coolant_sender: coolant.state,
+                    fuel_sender: fuel.state,
+                    oil_sender: oil.state,
+                    reference: reference.state.or(reference2.state),
+                },
+                dt_ms,
+            )
+        } else {
+            Alerts::default()
+        };
+        // Alerted panels flash at 1 Hz using the controller's invert
+        let flash_on = now.ticks() / 500_000 % 2 == 0;
+
+        for (panel, pager) in pagers.iter_mut().enumerate() {
+            let device = DISPLAYS[panel];
+            let page = pager.update(dt_ms, alerts);
+            if !status.is_present(device) {
+                continue;
+            }
+            let alert = alerts.highest_on(page);
+            let fault = status.fault_for(device);
+            match devices.show(
+                panel,
+                page,
+                alert,
+                fault,
+                flash_on,
+                &snapshot,
+                &settings.gauges,
+            ) {
+                Ok(()) => faults.cleared(device),
+                Err(e) => devices.recover(&mut faults, e),
+            }
+            // A flush takes long enough for a conversion or two
+            devices.acquire_all(&mut faults, now_ms(), &mut codes);
+        }
+
+        // Missing panels alone don't count against the loop; see
+        // `FaultLog::is_progressing`
+        if faults.is_progressing() {
+            watchdog.feed();
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! Panic handler that leaves the message for the next boot.
+
+use core::{fmt::Write, mem::MaybeUninit, panic::PanicInfo, ptr::addr_of_mut};
+
+use hardbody_core::{CrashRecord, CrashReport};
+
+use crate::reset;
+
+/// `.uninit` isn't zeroed by the runtime, so this survives a reset.
+#[link_section = ".uninit.CRASH"]
+static mut CRASH: MaybeUninit<CrashRecord> = MaybeUninit::uninit();
+
+fn record() -> &'static mut CrashRecord {
+    // Every bit pattern is a valid `CrashRecord`; `take` decides whether
+    // it holds a message
+    unsafe { (*addr_of_mut!(CRASH)).assume_init_mut() }
+}
+
+/// The message from before the last reset, if it was a panic. Clears it,
+/// so call once at boot.
+pub fn take() -> Option<CrashReport> {
+    record().take()
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+    let record = record();
+    record.begin();
+    write!(record, "{}", info).ok();
+    record.seal();
+    reset::panic_reset()
+}
@@ -0,0 +1,117 @@
+//! Tach and VSS pulse capture.
+//!
+//! Both inputs are spare GPIOs behind open-collector conditioners, so a
+//! pulse pulls the pin low. A falling-edge interrupt stamps each edge with
+//! the low word of the 1 MHz timer and feeds it to the core [`Tachometer`]
+//! or [`Speedometer`].
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use hardbody_core::{SpeedConfig, SpeedReading, Speedometer, TachConfig, TachReading, Tachometer};
+use rp2040_hal::{
+    gpio::{
+        bank0::{Gpio20, Gpio5},
+        FunctionSioInput,
+        Interrupt::EdgeLow,
+        Pin, PullUp,
+    },
+    pac::{self, interrupt},
+    timer::Timer,
+};
+
+/// The QT Py `TX` pad.
+pub type TachPin = Pin<Gpio20, FunctionSioInput, PullUp>;
+/// The QT Py `RX` pad.
+pub type VssPin = Pin<Gpio5, FunctionSioInput, PullUp>;
+
+/// Periods averaged per reading; eight is four revolutions on a 4-cylinder.
+const TACH_AVERAGE: usize = 8;
+/// Two speedo cable turns at the default calibration.
+const VSS_AVERAGE: usize = 8;
+
+struct Capture {
+    timer: Timer,
+    tach_pin: TachPin,
+    tach: Tachometer<TACH_AVERAGE>,
+    vss_pin: VssPin,
+    speed: Speedometer<VSS_AVERAGE>,
+}
+
+static CAPTURE: Mutex<RefCell<Option<Capture>>> = Mutex::new(RefCell::new(None));
+
+/// What the pulse inputs measured since the last [`read`].
+pub struct Pulses {
+    pub tach: TachReading,
+    pub speed: SpeedReading,
+    /// VSS pulses to add to the odometer
+    pub vss_pulses: u32,
+}
+
+/// Arms the edge interrupts; readings are zero until this is called.
+pub fn start(
+    tach_pin: TachPin,
+    vss_pin: VssPin,
+    timer: Timer,
+    tach: TachConfig,
+    speed: SpeedConfig,
+) {
+    tach_pin.set_interrupt_enabled(EdgeLow, true);
+    vss_pin.set_interrupt_enabled(EdgeLow, true);
+    critical_section::with(|cs| {
+        CAPTURE.borrow_ref_mut(cs).replace(Capture {
+            timer,
+            tach_pin,
+            tach: Tachometer::new(tach),
+            vss_pin,
+            speed: Speedometer::new(speed),
+        });
+    });
+    unsafe { pac::NVIC::unmask(pac::Interrupt::IO_IRQ_BANK0) };
+}
+
+/// Swaps in new calibration, e.g. after a console edit. Averaging restarts;
+/// VSS pulses not yet read are kept for the odometer.
+pub fn configure(tach: TachConfig, speed: SpeedConfig) {
+    critical_section::with(|cs| {
+        if let Some(c) = CAPTURE.borrow_ref_mut(cs).as_mut() {
+            c.tach = Tachometer::new(tach);
+            c.speed.configure(speed);
+        }
+    });
+}
+
+pub fn read() -> Pulses {
+    critical_section::with(|cs| match CAPTURE.borrow_ref_mut(cs).as_mut() {
+        Some(c) => {
+            let now = c.timer.get_counter_low();
+            Pulses {
+                tach: c.tach.read(now),
+                speed: c.speed.read(now),
+                vss_pulses: c.speed.take_pulses(),
+            }
+        }
+        None => Pulses {
+            tach: TachReading::default(),
+            speed: SpeedReading::default(),
+            vss_pulses: 0,
+        },
+    })
+}
+
+#[interrupt]
+fn IO_IRQ_BANK0() {
+    critical_section::with(|cs| {
+        if let Some(c) = CAPTURE.borrow_ref_mut(cs).as_mut() {
+            let now = c.timer.get_counter_low();
+            if c.tach_pin.interrupt_status(EdgeLow) {
+                c.tach.on_pulse(now);
+                c.tach_pin.clear_interrupt(EdgeLow);
+            }
+            if c.vss_pin.interrupt_status(EdgeLow) {
+                c.speed.on_pulse(now);
+                c.vss_pin.clear_interrupt(EdgeLow);
+            }
+        }
+    });
+}
@@ -0,0 +1,59 @@
+//! Reset reason and counts, kept in the watchdog scratch registers.
+
+use hardbody_core::{
+    reset::{PANIC_PENDING, SOFTWARE_PENDING},
+    ResetFlags, ResetLog,
+};
+use rp2040_hal::{
+    pac::{self, SCB},
+    watchdog::{ScratchRegister, Watchdog},
+};
+
+const SCRATCH: [ScratchRegister; 4] = [
+    ScratchRegister::Scratch0,
+    ScratchRegister::Scratch1,
+    ScratchRegister::Scratch2,
+    ScratchRegister::Scratch3,
+];
+
+/// Reads why the chip reset and counts it. Call before the watchdog is
+/// started.
+pub fn on_boot(watchdog: &mut Watchdog, chip: &pac::VREG_AND_CHIP_RESET) -> ResetLog {
+    // `Watchdog` owns the block but has no getter for REASON; reading it
+    // has no side effects
+    let reason = unsafe { (*pac::WATCHDOG::ptr()).reason().read() };
+    let chip_reset = chip.chip_reset().read();
+    let flags = ResetFlags {
+        watchdog_timer: reason.timer().bit_is_set(),
+        power_on: chip_reset.had_por().bit_is_set(),
+        run_pin: chip_reset.had_run().bit_is_set(),
+    };
+
+    let mut scratch = SCRATCH.map(|reg| watchdog.read_scratch(reg));
+    let log = ResetLog::on_boot(&mut scratch, flags);
+    for (reg, value) in SCRATCH.into_iter().zip(scratch) {
+        watchdog.write_scratch(reg, value);
+    }
+    log
+}
+
+/// Resets the chip, noting first that it was on purpose.
+pub fn software_reset() -> ! {
+    reset_with(SOFTWARE_PENDING)
+}
+
+/// Resets the chip from the panic handler.
+pub fn panic_reset() -> ! {
+    reset_with(PANIC_PENDING)
+}
+
+#[inline(never)]
+fn reset_with(pending: u32) -> ! {
+    // Nothing else touches scratch 3, and we're about to reset anyway
+    unsafe {
+        (*pac::WATCHDOG::ptr())
+            .scratch3()
+            .write(|w| w.bits(pending))
+    };
+    SCB::sys_reset()
+}
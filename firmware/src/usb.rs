@@ -0,0 +1,167 @@
+//! USB CDC-ACM serial console.
+//!
+//! USB has to be serviced within milliseconds, far faster than the main
+//! loop turns over, so the device is polled from `USBCTRL_IRQ`. The
+//! interrupt echoes typing and collects one command line at a time; the
+//! main loop picks it up with [`take_line`] and writes the reply through
+//! [`Reply`], which is buffered and drained as the host reads it.
+//!
+//! The class itself is `usbd-serial`'s, so hosts bind their stock serial
+//! driver and the console shows up as `/dev/ttyACM*` or a COM port with
+//! nothing to install.
+
+use core::{cell::RefCell, fmt};
+
+use critical_section::Mutex;
+use hardbody_core::{LineEditor, LineError};
+use rp2040_hal::{
+    clocks::UsbClock,
+    pac::{self, interrupt, RESETS, USBCTRL_DPRAM, USBCTRL_REGS},
+    usb::UsbBus,
+};
+use usb_device::{
+    bus::UsbBusAllocator,
+    device::{StringDescriptors, UsbDevice, UsbDeviceBuilder, UsbVidPid},
+};
+use usbd_serial::{SerialPort, USB_CLASS_CDC};
+
+/// Longest command line accepted.
+const LINE_LEN: usize = 96;
+/// Reply bytes buffered for the host; `settings` is the longest at ~1.8K,
+/// far more than the port's own buffer holds.
+const TX_LEN: usize = 3072;
+
+/// A complete command waiting for the main loop.
+pub struct Line {
+    buf: [u8; LINE_LEN],
+    len: usize,
+}
+
+impl Line {
+    pub fn as_str(&self) -> &str {
+        // Only ever filled from a `&str`
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+}
+
+struct Console {
+    device: UsbDevice<'static, UsbBus>,
+    serial: SerialPort<'static, UsbBus>,
+    editor: LineEditor<LINE_LEN>,
+    ready: Option<Line>,
+    tx: [u8; TX_LEN],
+    tx_len: usize,
+}
+
+impl Console {
+    fn queue(&mut self, bytes: &[u8]) {
+        let n = bytes.len().min(TX_LEN - self.tx_len);
+        self.tx[self.tx_len..self.tx_len + n].copy_from_slice(&bytes[..n]);
+        self.tx_len += n;
+    }
+
+    fn flush(&mut self) {
+        if self.tx_len == 0 {
+            return;
+        }
+        // WouldBlock just means the port's buffer is full for now
+        let n = self.serial.write(&self.tx[..self.tx_len]).unwrap_or(0);
+        self.tx.copy_within(n..self.tx_len, 0);
+        self.tx_len -= n;
+    }
+
+    fn poll(&mut self) {
+        if !self.device.poll(&mut [&mut self.serial]) {
+            self.flush();
+            return;
+        }
+        let mut rx = [0; 64];
+        let n = self.serial.read(&mut rx).unwrap_or(0);
+        for &byte in &rx[..n] {
+            match byte {
+                b'\r' | b'\n' => self.queue(b"\r\n"),
+                0x08 | 0x7F => self.queue(b"\x08 \x08"),
+                _ => self.queue(&[byte]),
+            }
+            let complaint: &[u8] = match self.editor.feed(byte) {
+                Some(Ok(line)) if self.ready.is_none() => {
+                    let mut buf = [0; LINE_LEN];
+                    buf[..line.len()].copy_from_slice(line.as_bytes());
+                    self.ready = Some(Line {
+                        buf,
+                        len: line.len(),
+                    });
+                    b""
+                }
+                Some(Ok(_)) => b"busy\r\n",
+                Some(Err(LineError::TooLong)) => b"line too long\r\n",
+                Some(Err(LineError::NotText)) => b"not text\r\n",
+                None => b"",
+            };
+            self.queue(complaint);
+        }
+        self.flush();
+    }
+}
+
+static CONSOLE: Mutex<RefCell<Option<Console>>> = Mutex::new(RefCell::new(None));
+
+/// Brings up the USB device and starts servicing it from the interrupt.
+pub fn start(regs: USBCTRL_REGS, dpram: USBCTRL_DPRAM, clock: UsbClock, resets: &mut RESETS) {
+    let bus: &'static UsbBusAllocator<UsbBus> = cortex_m::singleton!(
+        : UsbBusAllocator<UsbBus> =
+            UsbBusAllocator::new(UsbBus::new(regs, dpram, clock, true, resets))
+    )
+    .unwrap();
+
+    let serial = SerialPort::new(bus);
+    let device = UsbDeviceBuilder::new(bus, UsbVidPid(0x16c0, 0x27dd))
+        .strings(&[StringDescriptors::default()
+            .manufacturer("Cogware")
+            .product("Hardbody Cluster")
+            .serial_number("HB01")])
+        .unwrap()
+        .device_class(USB_CLASS_CDC)
+        .build();
+
+    critical_section::with(|cs| {
+        CONSOLE.borrow_ref_mut(cs).replace(Console {
+            device,
+            serial,
+            editor: LineEditor::new(),
+            ready: None,
+            tx: [0; TX_LEN],
+            tx_len: 0,
+        });
+    });
+    unsafe { pac::NVIC::unmask(pac::Interrupt::USBCTRL_IRQ) };
+}
+
+/// The next command typed at the console, if any.
+pub fn take_line() -> Option<Line> {
+    critical_section::with(|cs| CONSOLE.borrow_ref_mut(cs).as_mut()?.ready.take())
+}
+
+/// Queues text for the host. Anything past a full buffer is dropped.
+pub struct Reply;
+
+impl fmt::Write for Reply {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        critical_section::with(|cs| {
+            if let Some(console) = CONSOLE.borrow_ref_mut(cs).as_mut() {
+                console.queue(s.as_bytes());
+                console.flush();
+            }
+        });
+        Ok(())
+    }
+}
+
+#[interrupt]
+fn USBCTRL_IRQ() {
+    critical_section::with(|cs| {
+        if let Some(console) = CONSOLE.borrow_ref_mut(cs).as_mut() {
+            console.poll();
+        }
+    });
+}
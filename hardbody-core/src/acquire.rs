@@ -0,0 +1,324 @@
+//! Sequencing ADS1115 conversions without waiting on them.
+//!
+//! An ADS1115 converts one input at a time, about 8 ms each at 128 SPS.
+//! Rather than start a conversion and spin until it's done, the loop asks
+//! [`Acquisition`] which input to start next, goes off to draw, and picks
+//! the result up on a later pass once the ALERT/RDY pin says it's ready, or
+//! once the conversion time has gone by if the pin never does.
+//!
+//! Moving the mux leaves the ADC's sampling capacitor charged from the last
+//! input. A low-impedance source recharges it within the conversion; a high
+//! one, like the battery divider, needs a conversion or two thrown away
+//! first. Each input says how many with [`InputConfig::settle`].
+//!
+//! Kept results go through the input's [`Pipeline`] before they show up in
+//! [`Acquisition::latest`].
+
+use crate::sampling::{FilterConfig, Pipeline, Stage};
+
+/// A single-ended ADS1115 input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    A0,
+    A1,
+    A2,
+    A3,
+}
+
+/// How one input is sampled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputConfig {
+    pub input: Input,
+    /// Time between conversions kept, so between readings when nothing
+    /// oversamples. Inputs that fall behind are served most overdue first.
+    pub period_ms: u32,
+    /// Conversions thrown away after the mux moves to this input
+    pub settle: u8,
+    pub filter: FilterConfig,
+}
+
+/// A conversion that hasn't finished in this many conversion times has
+/// failed.
+const TIMEOUT_CONVERSIONS: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Converting {
+    slot: usize,
+    started_ms: u32,
+    /// Result is only settling the input
+    discard: bool,
+}
+
+/// Conversion schedule and latest results for one ADC.
+#[derive(Clone, Debug)]
+pub struct Acquisition<const N: usize> {
+    inputs: [InputConfig; N],
+    conversion_ms: u32,
+    /// When each input is next wanted; `None` until first sampled
+    due_ms: [Option<u32>; N],
+    raw: [Option<i16>; N],
+    pipelines: [Pipeline; N],
+    latest: [Option<i16>; N],
+    /// Slot the mux was last moved to, `None` when unknown
+    mux: Option<usize>,
+    /// Conversions still to throw away on `mux`
+    settling: u8,
+    converting: Option<Converting>,
+}
+
+impl<const N: usize> Acquisition<N> {
+    /// `conversion_ms` is one conversion at the ADC's data rate, rounded up.
+    pub const fn new(inputs: [InputConfig; N], conversion_ms: u32) -> Self {
+        assert!(N > 0, "an ADC needs at least one input");
+        let mut pipelines = [Pipeline::new(FilterConfig::RAW); N];
+        let mut i = 0;
+        while i < N {
+            pipelines[i] = Pipeline::new(inputs[i].filter);
+            i += 1;
+        }
+        Self {
+            inputs,
+            conversion_ms,
+            due_ms: [None; N],
+            raw: [None; N],
+            pipelines,
+            latest: [None; N],
+            mux: None,
+            settling: 0,
+            converting: None,
+        }
+    }
+
+    /// The input to start converting now, if the ADC is idle and something
+    /// is due.
+    pub fn start(&mut self, now_ms: u32) -> Option<Input> {
+        if self.converting.is_some() {
+            return None;
+        }
+        let slot = match self.mux {
+            // Finish settling rather than waste it
+            Some(slot) if self.settling > 0 => slot,
+            _ => self.most_overdue(now_ms)?,
+        };
+        if self.mux != Some(slot) {
+            self.mux = Some(slot);
+            self.settling = self.inputs[slot].settle;
+        }
+        self.converting = Some(Converting {
+            slot,
+            started_ms: now_ms,
+            discard: self.settling > 0,
+        });
+        Some(self.inputs[slot].input)
+    }
+
+    fn most_overdue(&self, now_ms: u32) -> Option<usize> {
+        (0..N)
+            .map(|slot| {
+                let overdue = match self.due_ms[slot] {
+                    Some(due) => now_ms.wrapping_sub(due) as i32,
+                    // Never sampled beats anything merely late
+                    None => i32::MAX,
+                };
+                (slot, overdue)
+            })
+            .filter(|&(_, overdue)| overdue >= 0)
+            // `max_by_key` keeps the last of equals; earlier slots win ties
+            .rev()
+            .max_by_key(|&(_, overdue)| overdue)
+            .map(|(slot, _)| slot)
+    }
+
+    /// The input being converted.
+    pub fn converting(&self) -> Option<Input> {
+        self.converting.map(|c| self.inputs[c.slot].input)
+    }
+
+    /// Whether the conversion in progress should be finished by now.
+    /// `ready` is the ALERT/RDY pin, asserted.
+    pub fn should_read(&self, now_ms: u32, ready: bool) -> bool {
+        match self.converting {
+            Some(c) => ready || now_ms.wrapping_sub(c.started_ms) >= self.conversion_ms,
+            None => false,
+        }
+    }
+
+    /// Whether the conversion in progress has taken too long to be coming.
+    pub fn timed_out(&self, now_ms: u32) -> bool {
+        match self.converting {
+            Some(c) => {
+                now_ms.wrapping_sub(c.started_ms) >= self.conversion_ms * TIMEOUT_CONVERSIONS
+            }
+            None => false,
+        }
+    }
+
+    /// Takes the result of the conversion in progress. Returns whether it
+    /// was kept rather than thrown away for settling.
+    pub fn finish(&mut self, now_ms: u32, code: i16) -> bool {
+        let Some(c) = self.converting.take() else {
+            return false;
+        };
+        if c.discard {
+            self.settling = self.settling.saturating_sub(1);
+            return false;
+        }
+        self.raw[c.slot] = Some(code);
+        if let Some(filtered) = self.pipelines[c.slot].push(code) {
+            self.latest[c.slot] = Some(filtered);
+        }
+        self.due_ms[c.slot] = Some(now_ms.wrapping_add(self.inputs[c.slot].period_ms));
+        true
+    }
+
+    /// Forgets the conversion in progress and where the mux is, after an
+    /// error or a re-init. Results already taken are kept.
+    pub fn reset(&mut self) {
+        self.converting = None;
+        self.mux = None;
+        self.settling = 0;
+    }
+
+    /// The newest filtered reading for each input, in the order they were
+    /// configured.
+    pub fn latest(&self) -> [Option<i16>; N] {
+        self.latest
+    }
+
+    /// The newest kept conversion for each input, before filtering.
+    pub fn raw(&self) -> [Option<i16>; N] {
+        self.raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUTS: [InputConfig; 3] = [
+        InputConfig {
+            input: Input::A0,
+            period_ms: 100,
+            settle: 0,
+            filter: FilterConfig::RAW,
+        },
+        InputConfig {
+            input: Input::A2,
+            period_ms: 500,
+            settle: 1,
+            filter: FilterConfig::RAW,
+        },
+        InputConfig {
+            input: Input::A3,
+            period_ms: 100,
+            settle: 0,
+            filter: FilterConfig::RAW,
+        },
+    ];
+
+    /// Runs one conversion to completion, returning the input and whether
+    /// the result was kept.
+    fn convert(acq: &mut Acquisition<3>, now_ms: &mut u32, code: i16) -> Option<(Input, bool)> {
+        let input = acq.start(*now_ms)?;
+        *now_ms += 8;
+        assert!(acq.should_read(*now_ms, false));
+        Some((input, acq.finish(*now_ms, code)))
+    }
+
+    #[test]
+    fn never_sampled_inputs_go_first_in_order() {
+        let mut acq = Acquisition::new(INPUTS, 8);
+        let mut now = 0;
+        assert_eq!(convert(&mut acq, &mut now, 10), Some((Input::A0, true)));
+        // A2 settles once before it counts
+        assert_eq!(convert(&mut acq, &mut now, 99), Some((Input::A2, false)));
+        assert_eq!(convert(&mut acq, &mut now, 20), Some((Input::A2, true)));
+        assert_eq!(convert(&mut acq, &mut now, 30), Some((Input::A3, true)));
+        assert_eq!(acq.latest(), [Some(10), Some(20), Some(30)]);
+        // Nothing is due again until A0's period is up
+        assert_eq!(acq.start(now), None);
+        assert_eq!(acq.start(108), Some(Input::A0));
+    }
+
+    #[test]
+    fn per_input_rates() {
+        let mut acq = Acquisition::new(INPUTS, 8);
+        let mut counts = [0; 4];
+        let mut now = 0;
+        while now < 5_000 {
+            match convert(&mut acq, &mut now, 0) {
+                Some((input, true)) => counts[input as usize] += 1,
+                Some((_, false)) => {}
+                None => now += 1,
+            }
+        }
+        // A0 and A3 at ~10 Hz, A2 at ~2 Hz
+        assert!((45..=50).contains(&counts[0]), "{:?}", counts);
+        assert!((9..=10).contains(&counts[2]), "{:?}", counts);
+        assert!((45..=50).contains(&counts[3]), "{:?}", counts);
+    }
+
+    #[test]
+    fn staying_on_an_input_needs_no_settling() {
+        let only_battery = [INPUTS[1]];
+        let mut acq = Acquisition::new(only_battery, 8);
+        acq.start(0);
+        assert!(!acq.finish(8, 0));
+        acq.start(8);
+        assert!(acq.finish(16, 1));
+        acq.start(516);
+        assert!(acq.finish(524, 2), "mux never moved");
+    }
+
+    #[test]
+    fn ready_pin_beats_the_clock() {
+        let mut acq = Acquisition::new(INPUTS, 8);
+        acq.start(0);
+        assert!(!acq.should_read(3, false));
+        assert!(acq.should_read(3, true));
+        assert!(!acq.timed_out(31));
+        assert!(acq.timed_out(32));
+    }
+
+    #[test]
+    fn reset_resettles_the_mux() {
+        let mut acq = Acquisition::new(INPUTS, 8);
+        let mut now = 0;
+        convert(&mut acq, &mut now, 0);
+        assert_eq!(acq.start(now), Some(Input::A2));
+        acq.reset();
+        assert_eq!(acq.converting(), None);
+        assert_eq!(convert(&mut acq, &mut now, 0), Some((Input::A2, false)));
+        assert_eq!(convert(&mut acq, &mut now, 5), Some((Input::A2, true)));
+    }
+
+    #[test]
+    fn readings_are_filtered() {
+        let inputs = [InputConfig {
+            filter: FilterConfig {
+                median: 3,
+                oversample: 1,
+                ema_shift: 0,
+            },
+            ..INPUTS[0]
+        }];
+        let mut acq = Acquisition::new(inputs, 8);
+        for (i, code) in [100, 101, 30_000].into_iter().enumerate() {
+            acq.start(i as u32 * 200);
+            acq.finish(i as u32 * 200 + 8, code);
+        }
+        assert_eq!(acq.raw(), [Some(30_000)]);
+        assert_eq!(acq.latest(), [Some(101)]);
+    }
+
+    #[test]
+    fn survives_timer_wrap() {
+        let mut acq = Acquisition::new(INPUTS, 8);
+        let mut now = u32::MAX - 50;
+        for _ in 0..4 {
+            convert(&mut acq, &mut now, 1);
+        }
+        assert_eq!(acq.start(now), None);
+        assert_eq!(acq.start(now.wrapping_add(100)), Some(Input::A0));
+    }
+}
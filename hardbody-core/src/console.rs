@@ -0,0 +1,803 @@
+//! Line-based command shell for the USB serial port.
+//!
+//! The firmware feeds received bytes to a [`LineEditor`] and hands each
+//! complete line to [`run`], along with the latest readings and the live
+//! [`Settings`]. Anything that needs hardware (saving to flash, rebooting,
+//! re-arming filters) comes back as an [`Action`] for the firmware to carry
+//! out, so the whole shell runs on the host in tests.
+
+use core::fmt::{self, Write};
+
+use crate::{
+    curve::CurvePoint,
+    fault::{Device, FaultLog},
+    odometer::Trip,
+    reset::ResetLog,
+    sensors::{RailState, SenderState, Snapshot},
+    settings::Settings,
+    thermistor::{SteinhartHart, Thermistor},
+};
+
+/// Why a line was thrown away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineError {
+    TooLong,
+    NotText,
+}
+
+/// Collects bytes into lines, handling backspace.
+pub struct LineEditor<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    overflowed: bool,
+}
+
+impl<const N: usize> LineEditor<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Takes one received byte. Returns the line when `byte` ends one;
+    /// blank lines (e.g. the `\n` of a `\r\n`) are skipped.
+    pub fn feed(&mut self, byte: u8) -> Option<Result<&str, LineError>> {
+        match byte {
+            b'\r' | b'\n' => {
+                let len = core::mem::take(&mut self.len);
+                if core::mem::take(&mut self.overflowed) {
+                    return Some(Err(LineError::TooLong));
+                }
+                let line = core::str::from_utf8(&self.buf[..len])
+                    .map(str::trim)
+                    .map_err(|_| LineError::NotText);
+                match line {
+                    Ok("") => None,
+                    line => Some(line),
+                }
+            }
+            // Backspace / DEL
+            0x08 | 0x7F => {
+                self.len = self.len.saturating_sub(1);
+                None
+            }
+            _ if self.len == N => {
+                self.overflowed = true;
+                None
+            }
+            _ => {
+                self.buf[self.len] = byte;
+                self.len += 1;
+                None
+            }
+        }
+    }
+}
+
+impl<const N: usize> Default for LineEditor<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A calibration value as typed and shown on the console.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Number(f32),
+    /// Curve point, typed as `x,y`
+    Point(f32, f32),
+}
+
+impl Value {
+    fn parse(s: &str) -> Option<Self> {
+        match s.split_once(',') {
+            Some((x, y)) => Some(Value::Point(x.trim().parse().ok()?, y.trim().parse().ok()?)),
+            None => s.parse().ok().map(Value::Number),
+        }
+    }
+
+    fn real(self) -> Result<f32, &'static str> {
+        match self {
+            Value::Number(n) if n.is_finite() => Ok(n),
+            _ => Err("expected a number"),
+        }
+    }
+
+    fn count(self) -> Result<u32, &'static str> {
+        match self {
+            Value::Number(n) if n >= 0.0 && n <= u32::MAX as f32 && n == (n as u32) as f32 => {
+                Ok(n as u32)
+            }
+            _ => Err("expected a whole number"),
+        }
+    }
+
+    fn point(self) -> Result<CurvePoint, &'static str> {
+        match self {
+            Value::Point(x, y) if x.is_finite() && y.is_finite() => Ok(CurvePoint { x, y }),
+            _ => Err("expected x,y"),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Point(x, y) => write!(f, "{},{}", x, y),
+        }
+    }
+}
+
+/// One named calibration value in [`Settings`].
+pub struct Field {
+    pub name: &'static str,
+    get: fn(&Settings) -> Value,
+    set: fn(&mut Settings, Value) -> Result<(), &'static str>,
+}
+
+impl Field {
+    pub fn get(&self, settings: &Settings) -> Value {
+        (self.get)(settings)
+    }
+
+    /// Applies `value`, leaving `settings` untouched if it's rejected.
+    pub fn set(&self, settings: &mut Settings, value: Value) -> Result<(), &'static str> {
+        let mut edited = *settings;
+        (self.set)(&mut edited, value)?;
+        if !edited.is_sane() {
+            return Err("out of range");
+        }
+        *settings = edited;
+        Ok(())
+    }
+
+    pub fn find(name: &str) -> Option<&'static Field> {
+        FIELDS.iter().find(|f| f.name == name)
+    }
+}
+
+macro_rules! real {
+    ($name:literal, $($path:ident).+) => {
+        Field {
+            name: $name,
+            get: |s| Value::Number(s.$($path).+),
+            set: |s, v| {
+                s.$($path).+ = v.real()?;
+                Ok(())
+            },
+        }
+    };
+}
+
+macro_rules! count {
+    ($name:literal, $($path:ident).+) => {
+        Field {
+            name: $name,
+            get: |s| Value::Number(s.$($path).+ as f32),
+            set: |s, v| {
+                s.$($path).+ = v.count()?;
+                Ok(())
+            },
+        }
+    };
+}
+
+macro_rules! point {
+    ($name:literal, $curve:ident, $index:literal) => {
+        Field {
+            name: $name,
+            get: |s| {
+                let p = s.$curve.points()[$index];
+                Value::Point(p.x, p.y)
+            },
+            set: |s, v| {
+                s.$curve
+                    .set_point($index, v.point()?)
+                    .then_some(())
+                    .ok_or("x must stay between its neighbours")
+            },
+        }
+    };
+}
+
+fn beta_param(s: &Settings, beta_not_r25: bool) -> Result<f32, &'static str> {
+    match s.thermistor {
+        Thermistor::Beta { beta, r25 } => Ok(if beta_not_r25 { beta } else { r25 }),
+        Thermistor::SteinhartHart(_) => Err("thermistor is Steinhart-Hart"),
+    }
+}
+
+/// Every value `get`/`set` can reach, in the order `settings` lists them.
+pub static FIELDS: &[Field] = &[
+    real!("circuit.pull_up", circuit.pull_up_ohms),
+    real!("circuit.pull_up_tol", circuit.pull_up_tolerance),
+    real!("circuit.batt_r1", circuit.battery_r1_ohms),
+    real!("circuit.batt_r2", circuit.battery_r2_ohms),
+    Field {
+        name: "therm.beta",
+        get: |s| Value::Number(beta_param(s, true).unwrap_or(f32::NAN)),
+        set: |s, v| {
+            let r25 = beta_param(s, false)?;
+            s.thermistor = Thermistor::Beta {
+                beta: v.real()?,
+                r25,
+            };
+            Ok(())
+        },
+    },
+    Field {
+        name: "therm.r25",
+        get: |s| Value::Number(beta_param(s, false).unwrap_or(f32::NAN)),
+        set: |s, v| {
+            let beta = beta_param(s, true)?;
+            s.thermistor = Thermistor::Beta {
+                beta,
+                r25: v.real()?,
+            };
+            Ok(())
+        },
+    },
+    // Setting any coefficient switches a Beta thermistor to Steinhart-Hart.
+    Field {
+        name: "therm.a",
+        get: |s| Value::Number(s.thermistor.steinhart_hart().a),
+        set: |s, v| {
+            let sh = s.thermistor.steinhart_hart();
+            s.thermistor = Thermistor::SteinhartHart(SteinhartHart { a: v.real()?, ..sh });
+            Ok(())
+        },
+    },
+    Field {
+        name: "therm.b",
+        get: |s| Value::Number(s.thermistor.steinhart_hart().b),
+        set: |s, v| {
+            let sh = s.thermistor.steinhart_hart();
+            s.thermistor = Thermistor::SteinhartHart(SteinhartHart { b: v.real()?, ..sh });
+            Ok(())
+        },
+    },
+    Field {
+        name: "therm.c",
+        get: |s| Value::Number(s.thermistor.steinhart_hart().c),
+        set: |s, v| {
+            let sh = s.thermistor.steinhart_hart();
+            s.thermistor = Thermistor::SteinhartHart(SteinhartHart { c: v.real()?, ..sh });
+            Ok(())
+        },
+    },
+    point!("fuel.0", fuel_curve, 0),
+    point!("fuel.1", fuel_curve, 1),
+    point!("fuel.2", fuel_curve, 2),
+    point!("fuel.3", fuel_curve, 3),
+    point!("fuel.4", fuel_curve, 4),
+    point!("fuel.5", fuel_curve, 5),
+    point!("fuel.6", fuel_curve, 6),
+    point!("fuel.7", fuel_curve, 7),
+    point!("fuel.8", fuel_curve, 8),
+    point!("oil.0", oil_curve, 0),
+    point!("oil.1", oil_curve, 1),
+    point!("oil.2", oil_curve, 2),
+    point!("oil.3", oil_curve, 3),
+    point!("oil.4", oil_curve, 4),
+    count!("slosh.tau_ms", slosh.tau_ms),
+    count!("slosh.settle_tau_ms", slosh.settle_tau_ms),
+    count!("slosh.settle_ms", slosh.settle_ms),
+    real!("slosh.refuel_step", slosh.refuel_step),
+    count!("slosh.refuel_hold_ms", slosh.refuel_hold_ms),
+    real!("warn.oil.trip", warnings.low_oil_pressure.trip),
+    real!("warn.oil.clear", warnings.low_oil_pressure.clear),
+    count!("warn.oil.hold_ms", warnings.low_oil_pressure.hold_ms),
+    real!("warn.hot.trip", warnings.overheat.trip),
+    real!("warn.hot.clear", warnings.overheat.clear),
+    count!("warn.hot.hold_ms", warnings.overheat.hold_ms),
+    real!("warn.volts.trip", warnings.low_voltage.trip),
+    real!("warn.volts.clear", warnings.low_voltage.clear),
+    count!("warn.volts.hold_ms", warnings.low_voltage.hold_ms),
+    real!("warn.fuel.trip", warnings.low_fuel.trip),
+    real!("warn.fuel.clear", warnings.low_fuel.clear),
+    count!("warn.fuel.hold_ms", warnings.low_fuel.hold_ms),
+    real!("tach.ppr", tach.pulses_per_rev),
+    count!("tach.min_period_us", tach.min_period_us),
+    count!("tach.timeout_us", tach.timeout_us),
+    count!("speed.ppm", speed.pulses_per_mile),
+    count!("speed.min_period_us", speed.min_period_us),
+    count!("speed.timeout_us", speed.timeout_us),
+    real!("gauge.temp_min", gauges.temp_min_f),
+    real!("gauge.temp_max", gauges.temp_max_f),
+];
+
+/// The newest ADS1115 codes before filtering, `None` for unused channels
+/// and before the first conversion.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RawCodes {
+    /// First ADC (ADDR → GND), A0..A3
+    pub adc1: [Option<i16>; 4],
+    /// Second ADC (ADDR → VDD), A0..A3
+    pub adc2: [Option<i16>; 4],
+}
+
+/// What the shell can see and change.
+pub struct Context<'a> {
+    pub settings: &'a mut Settings,
+    pub snapshot: &'a Snapshot,
+    pub raw: &'a RawCodes,
+    pub faults: &'a FaultLog,
+    pub resets: &'a ResetLog,
+    /// Panic message from before the last reset
+    pub crash: Option<&'a str>,
+}
+
+/// Follow-up work for the firmware after a command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    None,
+    /// Rebuild anything configured from the settings
+    SettingsChanged,
+    /// Write the live settings to flash and report the result
+    Save,
+    Reboot,
+    ResetTrip(Trip),
+}
+
+const HELP: &str = "\
+help               this list\r
+raw                ADC codes per channel\r
+errors             I2C failure counts\r
+resets             last reset reason and counts\r
+panic              message from the last panic\r
+read               converted readings\r
+settings           every calibration value\r
+get <name>         one value\r
+set <name> <value> change a value (curve points as x,y)\r
+defaults           back to compiled defaults\r
+save               write settings to flash\r
+trip reset <a|b>   zero a trip meter\r
+reboot             restart the cluster\r
+";
+
+/// Runs one command line, writing the reply to `out`.
+pub fn run<W: Write>(line: &str, ctx: &mut Context, out: &mut W) -> Result<Action, fmt::Error> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or("");
+    let args = (words.next(), words.next(), words.next());
+
+    match (command, args) {
+        ("help" | "?", (None, _, _)) => out.write_str(HELP)?,
+        ("raw", (None, _, _)) => write_raw(out, ctx.raw)?,
+        ("read", (None, _, _)) => write_readings(out, ctx.snapshot)?,
+        ("errors", (None, _, _)) => write_faults(out, ctx.faults)?,
+        ("panic", (None, _, _)) => match ctx.crash {
+            Some(text) => {
+                for line in text.lines() {
+                    write!(out, "{}\r\n", line)?;
+                }
+            }
+            None => out.write_str("no panic since power-on\r\n")?,
+        },
+        ("resets", (None, _, _)) => write!(
+            out,
+            "last reset {}\r\nwatchdog resets {}\r\nsoftware resets {}\r\n",
+            ctx.resets.reason.name(),
+            ctx.resets.watchdog,
+            ctx.resets.software
+        )?,
+        ("settings", (None, _, _)) => {
+            for field in FIELDS {
+                write!(out, "{} = {}\r\n", field.name, field.get(ctx.settings))?;
+            }
+        }
+        ("get", (Some(name), None, _)) => match Field::find(name) {
+            Some(field) => write!(out, "{} = {}\r\n", field.name, field.get(ctx.settings))?,
+            None => write!(out, "unknown setting {}\r\n", name)?,
+        },
+        ("set", (Some(name), Some(value), None)) => {
+            let Some(field) = Field::find(name) else {
+                write!(out, "unknown setting {}\r\n", name)?;
+                return Ok(Action::None);
+            };
+            let result = Value::parse(value)
+                .ok_or("expected a number or x,y")
+                .and_then(|v| field.set(ctx.settings, v));
+            return match result {
+                Ok(()) => {
+                    write!(out, "{} = {}\r\n", field.name, field.get(ctx.settings))?;
+                    Ok(Action::SettingsChanged)
+                }
+                Err(e) => {
+                    write!(out, "{}: {}\r\n", field.name, e)?;
+                    Ok(Action::None)
+                }
+            };
+        }
+        ("defaults", (None, _, _)) => {
+            *ctx.settings = Settings::default();
+            out.write_str("defaults loaded, not saved\r\n")?;
+            return Ok(Action::SettingsChanged);
+        }
+        ("save", (None, _, _)) => return Ok(Action::Save),
+        ("reboot", (None, _, _)) => return Ok(Action::Reboot),
+        ("trip", (Some("reset"), Some(which), None)) => match which {
+            "a" | "A" => return Ok(Action::ResetTrip(Trip::A)),
+            "b" | "B" => return Ok(Action::ResetTrip(Trip::B)),
+            _ => out.write_str("trip is a or b\r\n")?,
+        },
+        _ => write!(out, "? {} (try help)\r\n", line)?,
+    }
+    Ok(Action::None)
+}
+
+fn write_raw<W: Write>(out: &mut W, raw: &RawCodes) -> fmt::Result {
+    for (adc, codes) in [("adc1", &raw.adc1), ("adc2", &raw.adc2)] {
+        for (channel, code) in codes.iter().enumerate() {
+            if let Some(code) = code {
+                write!(out, "{} A{} {}\r\n", adc, channel, code)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_faults<W: Write>(out: &mut W, faults: &FaultLog) -> fmt::Result {
+    for device in Device::ALL {
+        write!(
+            out,
+            "{:<8} {:<7} {} failed, {} in a row\r\n",
+            device.name(),
+            if faults.status().is_present(device) {
+                "ok"
+            } else {
+                "missing"
+            },
+            faults.total(device),
+            faults.consecutive(device)
+        )?;
+    }
+    write!(out, "bus recoveries {}\r\n", faults.bus_recoveries())?;
+    match faults.last() {
+        Some(e) => write!(out, "last {}\r\n", e),
+        None => Ok(()),
+    }
+}
+
+/// A sender reading with the range the pull-up's tolerance allows, or
+/// what's wrong with its wiring instead.
+fn write_sender<W: Write>(
+    out: &mut W,
+    name: &str,
+    state: SenderState,
+    value: fmt::Arguments,
+    ohms: f32,
+) -> fmt::Result {
+    match state {
+        SenderState::Ok => write!(out, "{:<7} {} ({:.1} ohm)\r\n", name, value, ohms),
+        state => write!(out, "{:<7} {} ({:.1} ohm)\r\n", name, state.name(), ohms),
+    }
+}
+
+fn write_readings<W: Write>(out: &mut W, s: &Snapshot) -> fmt::Result {
+    write_sender(
+        out,
+        "coolant",
+        s.coolant.state,
+        format_args!(
+            "{:.1} F [{:.1}..{:.1}]",
+            s.coolant.fahrenheit, s.coolant.bounds.min, s.coolant.bounds.max
+        ),
+        s.coolant.ohms,
+    )?;
+    match s.reference.state {
+        RailState::Ok => write!(out, "ref     {:.3} V\r\n", s.reference.volts)?,
+        state => write!(
+            out,
+            "ref     {:.3} V {}\r\n",
+            s.reference.volts,
+            state.name()
+        )?,
+    }
+    write_sender(
+        out,
+        "fuel",
+        s.fuel.state,
+        format_args!(
+            "{} % [{:.0}..{:.0}]",
+            s.fuel.percent, s.fuel.bounds.min, s.fuel.bounds.max
+        ),
+        s.fuel.ohms,
+    )?;
+    write!(out, "battery {:.2} V\r\n", s.battery.volts)?;
+    write_sender(
+        out,
+        "oil",
+        s.oil.state,
+        format_args!(
+            "{:.1} psi [{:.1}..{:.1}]",
+            s.oil.psi, s.oil.bounds.min, s.oil.bounds.max
+        ),
+        s.oil.ohms,
+    )?;
+    write!(out, "tach    {:.0} rpm\r\n", s.tach.rpm)?;
+    write!(out, "speed   {:.1} mph\r\n", s.speed.mph)?;
+    write!(
+        out,
+        "odo     {}  trip a {}  trip b {}\r\n",
+        s.odometer.total(),
+        s.odometer.trip(Trip::A),
+        s.odometer.trip(Trip::B)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sensors::{
+            BatteryReading, CoolantReading, FuelReading, MinMax, OilPressureReading,
+            ReferenceReading,
+        },
+        speed::SpeedReading,
+        tach::TachReading,
+        thermistor::DEFAULT_THERMISTOR,
+        ClusterError, Distance, Odometer, Operation, ResetReason,
+    };
+    use alloc::{string::String, vec::Vec};
+
+    const SNAPSHOT: Snapshot = Snapshot {
+        coolant: CoolantReading {
+            ohms: 52.1,
+            fahrenheit: 196.4,
+            bounds: MinMax {
+                min: 195.9,
+                max: 196.9,
+            },
+            state: SenderState::Ok,
+        },
+        coolant_range: None,
+        reference: ReferenceReading {
+            volts: 3.301,
+            state: RailState::Ok,
+        },
+        fuel: FuelReading {
+            ratio: 0.05,
+            ohms: 48.0,
+            percent: 48,
+            bounds: MinMax {
+                min: 47.2,
+                max: 49.4,
+            },
+            state: SenderState::Ok,
+        },
+        battery: BatteryReading { volts: 13.92 },
+        oil: OilPressureReading {
+            ratio: 0.08,
+            ohms: 88.0,
+            psi: 40.0,
+            bounds: MinMax {
+                min: 39.6,
+                max: 40.4,
+            },
+            state: SenderState::Ok,
+        },
+        tach: TachReading { rpm: 812.0 },
+        speed: SpeedReading { mph: 0.0 },
+        odometer: Odometer::new(Distance::from_tenths(1_234_567)),
+    };
+
+    const RESETS: ResetLog = ResetLog {
+        reason: ResetReason::Watchdog,
+        watchdog: 2,
+        software: 0,
+    };
+
+    /// Types `input` at the console and returns everything it printed plus
+    /// the actions it asked for.
+    fn script(input: &str, settings: &mut Settings) -> (String, Vec<Action>) {
+        let raw = RawCodes {
+            adc1: [Some(1_234), Some(1_100), Some(10_000), Some(26_400)],
+            adc2: [Some(2_000), None, None, Some(26_390)],
+        };
+        let mut faults = FaultLog::new();
+        faults.record(ClusterError::new(Device::Display1, Operation::Flush), false);
+        let mut editor = LineEditor::<64>::new();
+        let mut out = String::new();
+        let mut actions = Vec::new();
+        for byte in input.bytes() {
+            match editor.feed(byte) {
+                Some(Ok(line)) => {
+                    let mut ctx = Context {
+                        settings: &mut *settings,
+                        snapshot: &SNAPSHOT,
+                        raw: &raw,
+                        faults: &faults,
+                        resets: &RESETS,
+                        crash: Some("panicked at src/main.rs:210:5:\nboom"),
+                    };
+                    actions.push(run(line, &mut ctx, &mut out).unwrap());
+                }
+                Some(Err(e)) => out.push_str(&alloc::format!("{:?}\r\n", e)),
+                None => {}
+            }
+        }
+        (out, actions)
+    }
+
+    #[test]
+    fn line_editing() {
+        let mut settings = Settings::default();
+        let (out, _) = script("gte\x08\x08et tach.ppr\r\n\r\n", &mut settings);
+        assert_eq!(out, "tach.ppr = 2\r\n");
+
+        let long = "x".repeat(80) + "\rhelp\r";
+        let (out, _) = script(&long, &mut settings);
+        assert!(out.starts_with("TooLong\r\nhelp "), "{}", out);
+    }
+
+    #[test]
+    fn readings_show_faults() {
+        let mut snapshot = SNAPSHOT;
+        snapshot.reference = ReferenceReading {
+            volts: 2.901,
+            state: RailState::Low,
+        };
+        snapshot.fuel.state = SenderState::NoReference;
+        snapshot.oil.state = SenderState::Open;
+        let mut out = String::new();
+        write_readings(&mut out, &snapshot).unwrap();
+        assert!(out.contains("ref     2.901 V low\r\n"), "{}", out);
+        assert!(out.contains("fuel    no ref (48.0 ohm)\r\n"), "{}", out);
+        assert!(out.contains("oil     open (88.0 ohm)\r\n"), "{}", out);
+    }
+
+    #[test]
+    fn raw_and_readings() {
+        let mut settings = Settings::default();
+        let (out, _) = script("raw\r", &mut settings);
+        assert_eq!(
+            out,
+            "adc1 A0 1234\r\nadc1 A1 1100\r\nadc1 A2 10000\r\nadc1 A3 26400\r\n\
+             adc2 A0 2000\r\nadc2 A3 26390\r\n"
+        );
+
+        let (out, _) = script("read\r", &mut settings);
+        assert!(
+            out.contains("coolant 196.4 F [195.9..196.9] (52.1 ohm)\r\n"),
+            "{}",
+            out
+        );
+        assert!(out.contains("fuel    48 % [47..49] (48.0 ohm)\r\n"));
+        assert!(out.contains("oil     40.0 psi [39.6..40.4] (88.0 ohm)\r\n"));
+        assert!(out.contains("odo     123456.7  trip a 0.0  trip b 0.0\r\n"));
+
+        let (out, _) = script("errors\r", &mut settings);
+        assert!(
+            out.starts_with("adc1     ok      0 failed, 0 in a row\r\n"),
+            "{}",
+            out
+        );
+        assert!(out.contains("display1 ok      1 failed, 1 in a row\r\n"));
+        assert!(out.ends_with("bus recoveries 0\r\nlast display1 flush\r\n"));
+
+        let (out, _) = script("panic\r", &mut settings);
+        assert_eq!(out, "panicked at src/main.rs:210:5:\r\nboom\r\n");
+
+        let (out, _) = script("resets\r", &mut settings);
+        assert_eq!(
+            out,
+            "last reset watchdog\r\nwatchdog resets 2\r\nsoftware resets 0\r\n"
+        );
+    }
+
+    #[test]
+    fn set_and_get() {
+        let mut settings = Settings::default();
+        let (out, actions) = script(
+            "set speed.ppm 3756\rset fuel.4 50,47.5\rget fuel.4\r",
+            &mut settings,
+        );
+        assert_eq!(
+            out,
+            "speed.ppm = 3756\r\nfuel.4 = 50,47.5\r\nfuel.4 = 50,47.5\r\n"
+        );
+        assert_eq!(
+            actions,
+            [
+                Action::SettingsChanged,
+                Action::SettingsChanged,
+                Action::None
+            ]
+        );
+        assert_eq!(settings.speed.pulses_per_mile, 3_756);
+        assert_eq!(settings.fuel_curve.eval(50.0), 47.5);
+    }
+
+    #[test]
+    fn bad_values_leave_settings_alone() {
+        let mut settings = Settings::default();
+        let (out, actions) = script(
+            "set speed.ppm 12.5\rset fuel.4 200,1\rset circuit.batt_r2 0\rset slosh.tau_ms 0\r\
+             set therm.beta 0\rset nope 1\r",
+            &mut settings,
+        );
+        assert_eq!(
+            out,
+            "speed.ppm: expected a whole number\r\n\
+             fuel.4: x must stay between its neighbours\r\n\
+             circuit.batt_r2: out of range\r\n\
+             slosh.tau_ms: out of range\r\n\
+             therm.beta: out of range\r\n\
+             unknown setting nope\r\n"
+        );
+        assert!(actions.iter().all(|a| *a == Action::None));
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn thermistor_models() {
+        let mut settings = Settings::default();
+        // Switching to Steinhart-Hart starts from the equivalent of the Beta fit
+        let (_, _) = script("set therm.c 0\r", &mut settings);
+        let Thermistor::SteinhartHart(_) = settings.thermistor else {
+            panic!("{:?}", settings.thermistor);
+        };
+        for ohms in [50.0, 325.0, 2_000.0] {
+            let f = settings.thermistor.fahrenheit(ohms);
+            assert!(
+                (f - DEFAULT_THERMISTOR.fahrenheit(ohms)).abs() < 0.5,
+                "{}",
+                ohms
+            );
+        }
+
+        let (out, _) = script("set therm.beta 3900\r", &mut settings);
+        assert_eq!(out, "therm.beta: thermistor is Steinhart-Hart\r\n");
+    }
+
+    #[test]
+    fn every_field_round_trips_its_own_value() {
+        let mut settings = Settings::default();
+        for field in FIELDS {
+            let value = field.get(&settings);
+            if let Value::Number(n) = value {
+                if n.is_nan() {
+                    continue; // not applicable to the current model
+                }
+            }
+            if matches!(field.name, "therm.a" | "therm.b" | "therm.c") {
+                continue; // would switch the model; see thermistor_models
+            }
+            assert_eq!(field.set(&mut settings, value), Ok(()), "{}", field.name);
+        }
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn hardware_actions() {
+        let mut settings = Settings::default();
+        settings.gauges.temp_max_f = 250.0;
+        let (out, actions) = script(
+            "save\rtrip reset b\rtrip reset c\rdefaults\rreboot\r",
+            &mut settings,
+        );
+        assert_eq!(
+            actions,
+            [
+                Action::Save,
+                Action::ResetTrip(Trip::B),
+                Action::None,
+                Action::SettingsChanged,
+                Action::Reboot
+            ]
+        );
+        assert_eq!(out, "trip is a or b\r\ndefaults loaded, not saved\r\n");
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn unknown_commands() {
+        let mut settings = Settings::default();
+        let (out, _) = script("frobnicate\rget\r", &mut settings);
+        assert_eq!(out, "? frobnicate (try help)\r\n? get (try help)\r\n");
+    }
+}
@@ -0,0 +1,142 @@
+//! Panic messages kept across a reset.
+//!
+//! The panic handler writes the message into a [`CrashRecord`] placed in RAM
+//! that the runtime doesn't initialise, then resets. The next boot takes
+//! the message out, which also clears it. After a power cycle the record
+//! holds whatever the RAM powered up with, so it is only trusted if the
+//! marker, the length and the text all check out.
+
+use core::fmt;
+
+/// Longest message kept; six lines of 21 characters on a panel.
+pub const CRASH_TEXT_LEN: usize = 126;
+
+const MAGIC: u32 = 0x4352_5348;
+
+#[repr(C)]
+pub struct CrashRecord {
+    magic: u32,
+    len: u32,
+    text: [u8; CRASH_TEXT_LEN],
+}
+
+impl CrashRecord {
+    pub const fn new() -> Self {
+        Self {
+            magic: 0,
+            len: 0,
+            text: [0; CRASH_TEXT_LEN],
+        }
+    }
+
+    /// Starts a new message. Invalid until [`CrashRecord::seal`].
+    pub fn begin(&mut self) {
+        self.magic = 0;
+        self.len = 0;
+    }
+
+    pub fn seal(&mut self) {
+        self.magic = MAGIC;
+    }
+
+    /// The message from before the reset, if there is one, clearing it.
+    pub fn take(&mut self) -> Option<CrashReport> {
+        let valid = self.magic == MAGIC;
+        self.magic = 0;
+        let len = self.len as usize;
+        if !valid || len > CRASH_TEXT_LEN {
+            return None;
+        }
+        core::str::from_utf8(&self.text[..len]).ok()?;
+        Some(CrashReport {
+            text: self.text,
+            len,
+        })
+    }
+}
+
+impl Default for CrashRecord {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Anything past [`CRASH_TEXT_LEN`] is dropped, on a character boundary.
+impl fmt::Write for CrashRecord {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = self.len as usize;
+        let room = CRASH_TEXT_LEN.saturating_sub(len);
+        let mut n = s.len().min(room);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.text[len..len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n as u32;
+        Ok(())
+    }
+}
+
+/// A panic message recovered at boot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CrashReport {
+    text: [u8; CRASH_TEXT_LEN],
+    len: usize,
+}
+
+impl CrashReport {
+    pub fn as_str(&self) -> &str {
+        // Checked in `take`
+        core::str::from_utf8(&self.text[..self.len]).unwrap_or("")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn survives_until_taken() {
+        let mut record = CrashRecord::new();
+        record.begin();
+        write!(record, "panicked at src/main.rs:{}:5:\nboom", 210).unwrap();
+        record.seal();
+
+        let report = record.take().unwrap();
+        assert_eq!(report.as_str(), "panicked at src/main.rs:210:5:\nboom");
+        assert_eq!(record.take(), None);
+    }
+
+    #[test]
+    fn long_messages_are_cut_on_a_character() {
+        let mut record = CrashRecord::new();
+        record.begin();
+        record.write_str(&"x".repeat(CRASH_TEXT_LEN - 1)).unwrap();
+        record.write_str("°F").unwrap();
+        record.seal();
+        let report = record.take().unwrap();
+        assert_eq!(report.as_str().len(), CRASH_TEXT_LEN - 1);
+    }
+
+    #[test]
+    fn power_up_garbage_is_ignored() {
+        let mut record = CrashRecord {
+            magic: MAGIC,
+            len: 4000,
+            text: [0xAA; CRASH_TEXT_LEN],
+        };
+        assert_eq!(record.take(), None);
+
+        let mut record = CrashRecord {
+            magic: MAGIC,
+            len: 4,
+            text: [0xFF; CRASH_TEXT_LEN],
+        };
+        assert_eq!(record.take(), None);
+
+        let mut record = CrashRecord::new();
+        record.begin();
+        record.write_str("half written").unwrap();
+        assert_eq!(record.take(), None);
+    }
+}
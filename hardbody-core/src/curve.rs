@@ -0,0 +1,142 @@
+//! Piecewise-linear calibration tables.
+//!
+//! Senders rarely follow a clean formula once they're bolted into a real
+//! tank or block, so calibrations are stored as measured `(input, output)`
+//! pairs and interpolated between.
+
+/// One measured calibration point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CurvePoint {
+    /// Input, e.g. sender resistance in ohms
+    pub x: f32,
+    /// Output in the gauge's unit, e.g. percent
+    pub y: f32,
+}
+
+/// `N` calibration points sorted by ascending `x`.
+///
+/// Inputs outside the table clamp to the first or last `y`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Curve<const N: usize> {
+    points: [CurvePoint; N],
+}
+
+impl<const N: usize> Curve<N> {
+    /// Builds a curve from `(x, y)` pairs.
+    ///
+    /// Panics (at compile time for `const` tables) unless there are at least
+    /// two points and `x` is strictly increasing.
+    pub const fn new(pairs: [(f32, f32); N]) -> Self {
+        assert!(N >= 2, "a curve needs at least two points");
+        let mut points = [CurvePoint { x: 0.0, y: 0.0 }; N];
+        let mut i = 0;
+        while i < N {
+            if i > 0 {
+                assert!(pairs[i].0 > pairs[i - 1].0, "curve x must be increasing");
+            }
+            points[i] = CurvePoint {
+                x: pairs[i].0,
+                y: pairs[i].1,
+            };
+            i += 1;
+        }
+        Self { points }
+    }
+
+    /// Like [`Self::new`], but returns `None` instead of panicking, for
+    /// tables that come from storage or a console.
+    pub fn try_new(pairs: [(f32, f32); N]) -> Option<Self> {
+        let sorted = pairs.windows(2).all(|w| w[1].0 > w[0].0);
+        (N >= 2 && sorted).then(|| Self::new(pairs))
+    }
+
+    pub fn points(&self) -> &[CurvePoint; N] {
+        &self.points
+    }
+
+    /// Replaces one point, keeping the table sorted.
+    ///
+    /// Returns `false` (and leaves the curve unchanged) if `point` would not
+    /// sit strictly between its neighbours.
+    pub fn set_point(&mut self, index: usize, point: CurvePoint) -> bool {
+        if index >= N {
+            return false;
+        }
+        let after_prev = index == 0 || point.x > self.points[index - 1].x;
+        let before_next = index + 1 == N || point.x < self.points[index + 1].x;
+        if !(after_prev && before_next) {
+            return false;
+        }
+        self.points[index] = point;
+        true
+    }
+
+    /// Interpolated output for `x`.
+    pub fn eval(&self, x: f32) -> f32 {
+        let first = self.points[0];
+        let last = self.points[N - 1];
+        if x.is_nan() || x <= first.x {
+            return first.y;
+        }
+        if x >= last.x {
+            return last.y;
+        }
+        for pair in self.points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if x <= b.x {
+                return a.y + (b.y - a.y) * (x - a.x) / (b.x - a.x);
+            }
+        }
+        last.y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FALLING: Curve<3> = Curve::new([(0.0, 100.0), (10.0, 50.0), (30.0, 0.0)]);
+
+    #[test]
+    fn hits_points_exactly() {
+        for p in FALLING.points() {
+            assert_eq!(FALLING.eval(p.x), p.y);
+        }
+    }
+
+    #[test]
+    fn interpolates_within_segments() {
+        assert_eq!(FALLING.eval(5.0), 75.0);
+        assert_eq!(FALLING.eval(20.0), 25.0);
+    }
+
+    #[test]
+    fn clamps_outside_table() {
+        assert_eq!(FALLING.eval(-4.0), 100.0);
+        assert_eq!(FALLING.eval(1e6), 0.0);
+        assert_eq!(FALLING.eval(f32::NAN), 100.0);
+    }
+
+    #[test]
+    fn set_point_keeps_order() {
+        let mut curve = FALLING;
+        assert!(curve.set_point(1, CurvePoint { x: 12.0, y: 40.0 }));
+        assert_eq!(curve.eval(12.0), 40.0);
+        assert!(!curve.set_point(1, CurvePoint { x: 31.0, y: 40.0 }));
+        assert!(!curve.set_point(3, CurvePoint { x: 40.0, y: 0.0 }));
+        assert_eq!(curve.points()[1].x, 12.0);
+    }
+
+    #[test]
+    fn try_new_rejects_unsorted() {
+        assert!(Curve::try_new([(0.0, 1.0), (2.0, 0.0)]).is_some());
+        assert!(Curve::try_new([(2.0, 1.0), (0.0, 0.0)]).is_none());
+        assert!(Curve::try_new([(f32::NAN, 1.0), (0.0, 0.0)]).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_unsorted() {
+        Curve::new([(1.0, 0.0), (1.0, 1.0)]);
+    }
+}
@@ -0,0 +1,387 @@
+//! Peripheral failures and how hard to try fixing them.
+//!
+//! Every I2C device keeps a count of consecutive failures. A one-off glitch
+//! is simply retried on the next loop; if the device keeps failing it is
+//! re-initialised on its own, then the whole bus is recovered, and if none
+//! of that helps the device is written off as missing. The cluster carries
+//! on with whatever still answers and tries missing devices again every few
+//! seconds. Only when nothing is left is the cluster reset.
+
+use core::fmt;
+
+/// The devices on the I2C bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Device {
+    /// ADS1115 at ADDR → GND
+    Adc1,
+    /// ADS1115 at ADDR → VDD
+    Adc2,
+    /// SSD1306 at 0x3C
+    Display1,
+    /// SSD1306 at 0x3D
+    Display2,
+}
+
+impl Device {
+    pub const ALL: [Device; 4] = [
+        Device::Adc1,
+        Device::Adc2,
+        Device::Display1,
+        Device::Display2,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Device::Adc1 => "adc1",
+            Device::Adc2 => "adc2",
+            Device::Display1 => "display1",
+            Device::Display2 => "display2",
+        }
+    }
+
+    /// What a surviving panel says when this device is missing. Display 1
+    /// carries the coolant gauge and display 2 the fuel gauge.
+    pub fn fault_message(self) -> &'static str {
+        match self {
+            Device::Adc1 | Device::Adc2 => "ADC FAULT",
+            Device::Display1 => "TEMP DISPLAY FAULT",
+            Device::Display2 => "FUEL DISPLAY FAULT",
+        }
+    }
+}
+
+/// Which devices are answering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceStatus {
+    present: [bool; 4],
+}
+
+impl DeviceStatus {
+    pub const ALL_PRESENT: Self = Self { present: [true; 4] };
+
+    pub fn is_present(self, device: Device) -> bool {
+        self.present[device as usize]
+    }
+
+    pub fn missing(self) -> impl Iterator<Item = Device> {
+        Device::ALL
+            .into_iter()
+            .filter(move |&d| !self.is_present(d))
+    }
+
+    /// The missing device `panel` should warn about, ADCs first since they
+    /// make its own readings stale.
+    pub fn fault_for(self, panel: Device) -> Option<Device> {
+        self.missing().find(|&d| d != panel)
+    }
+}
+
+impl Default for DeviceStatus {
+    fn default() -> Self {
+        Self::ALL_PRESENT
+    }
+}
+
+/// What the device was asked to do when it failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Init,
+    Configure,
+    Read,
+    Draw,
+    Flush,
+    Invert,
+}
+
+impl Operation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Init => "init",
+            Operation::Configure => "configure",
+            Operation::Read => "read",
+            Operation::Draw => "draw",
+            Operation::Flush => "flush",
+            Operation::Invert => "invert",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClusterError {
+    pub device: Device,
+    pub operation: Operation,
+}
+
+impl ClusterError {
+    pub const fn new(device: Device, operation: Operation) -> Self {
+        Self { device, operation }
+    }
+}
+
+impl fmt::Display for ClusterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.device.name(), self.operation.name())
+    }
+}
+
+/// What the firmware should do about a failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// Try again next loop
+    Retry,
+    /// Re-initialise the failing device
+    Reinit,
+    /// Free the bus and re-initialise every device on it
+    RecoverBus,
+    /// Stop using the device; [`FaultLog::retry_due`] says when to try it
+    Offline,
+    /// Nothing is answering, reset the cluster
+    Reset,
+}
+
+/// Consecutive failures before each escalation.
+const REINIT_AFTER: u8 = 2;
+const RECOVER_BUS_AFTER: u8 = 4;
+const OFFLINE_AFTER: u8 = 6;
+/// How often missing devices are tried again.
+const RETRY_MISSING_MS: u32 = 5_000;
+
+/// Failure counts and presence per device.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FaultLog {
+    consecutive: [u8; 4],
+    total: [u32; 4],
+    bus_recoveries: u32,
+    last: Option<ClusterError>,
+    status: DeviceStatus,
+    since_retry_ms: u32,
+}
+
+impl FaultLog {
+    pub const fn new() -> Self {
+        Self {
+            consecutive: [0; 4],
+            total: [0; 4],
+            bus_recoveries: 0,
+            last: None,
+            status: DeviceStatus::ALL_PRESENT,
+            since_retry_ms: 0,
+        }
+    }
+
+    /// Counts a failure and says how to recover from it.
+    ///
+    /// `bus_stuck` is whether SDA was being held low afterwards. Nothing
+    /// short of freeing the bus will help then, so it skips straight there.
+    pub fn record(&mut self, error: ClusterError, bus_stuck: bool) -> Recovery {
+        let i = error.device as usize;
+        self.consecutive[i] = self.consecutive[i].saturating_add(1);
+        self.total[i] = self.total[i].saturating_add(1);
+        self.last = Some(error);
+        match self.consecutive[i] {
+            n if n >= OFFLINE_AFTER => {
+                self.status.present[i] = false;
+                match self.status.missing().count() {
+                    4 => Recovery::Reset,
+                    _ => Recovery::Offline,
+                }
+            }
+            n if n >= RECOVER_BUS_AFTER || bus_stuck => {
+                self.bus_recoveries = self.bus_recoveries.saturating_add(1);
+                Recovery::RecoverBus
+            }
+            n if n >= REINIT_AFTER => Recovery::Reinit,
+            _ => Recovery::Retry,
+        }
+    }
+
+    /// Call once `device` has worked again; escalation starts over.
+    pub fn cleared(&mut self, device: Device) {
+        self.consecutive[device as usize] = 0;
+        self.status.present[device as usize] = true;
+    }
+
+    /// Whether it's time to try re-initialising the missing devices.
+    pub fn retry_due(&mut self, dt_ms: u32) -> bool {
+        if self.status.missing().next().is_none() {
+            self.since_retry_ms = 0;
+            return false;
+        }
+        self.since_retry_ms = self.since_retry_ms.saturating_add(dt_ms);
+        if self.since_retry_ms < RETRY_MISSING_MS {
+            return false;
+        }
+        self.since_retry_ms = 0;
+        true
+    }
+
+    pub fn status(&self) -> DeviceStatus {
+        self.status
+    }
+
+    /// Failures since boot.
+    pub fn total(&self, device: Device) -> u32 {
+        self.total[device as usize]
+    }
+
+    /// Failures since it last worked.
+    pub fn consecutive(&self, device: Device) -> u8 {
+        self.consecutive[device as usize]
+    }
+
+    /// Whether a device is still in use although freeing the bus hasn't
+    /// brought it back, i.e. it's on its way to offline or a reset.
+    pub fn is_escalating(&self) -> bool {
+        Device::ALL
+            .into_iter()
+            .any(|d| self.status.is_present(d) && self.consecutive[d as usize] >= RECOVER_BUS_AFTER)
+    }
+
+    /// Whether a pass of the main loop counts as progress for the watchdog.
+    ///
+    /// Running on whatever still answers is normal operation, displays or
+    /// not; resetting for a missing device is [`Recovery::Reset`]'s call,
+    /// and only once nothing is left. So a pass counts unless a device is
+    /// escalating, which the next failure or two settles either way.
+    pub fn is_progressing(&self) -> bool {
+        self.status.missing().count() < Device::ALL.len() && !self.is_escalating()
+    }
+
+    pub fn bus_recoveries(&self) -> u32 {
+        self.bus_recoveries
+    }
+
+    /// The most recent failure from any device.
+    pub fn last(&self) -> Option<ClusterError> {
+        self.last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLUSH: ClusterError = ClusterError::new(Device::Display2, Operation::Flush);
+
+    #[test]
+    fn escalates_while_failures_continue() {
+        let mut log = FaultLog::new();
+        let steps: [Recovery; 7] = core::array::from_fn(|i| {
+            // Past the first bus recovery until the device is dropped
+            assert_eq!(log.is_escalating(), (4..6).contains(&i), "{}", i);
+            log.record(FLUSH, false)
+        });
+        assert_eq!(
+            steps,
+            [
+                Recovery::Retry,
+                Recovery::Reinit,
+                Recovery::Reinit,
+                Recovery::RecoverBus,
+                Recovery::RecoverBus,
+                Recovery::Offline,
+                Recovery::Offline,
+            ]
+        );
+        assert!(!log.status().is_present(Device::Display2));
+        assert!(!log.is_escalating());
+        assert_eq!(log.total(Device::Display2), 7);
+        assert_eq!(log.bus_recoveries(), 2);
+        assert_eq!(log.last(), Some(FLUSH));
+    }
+
+    #[test]
+    fn progress_does_not_need_a_display() {
+        let mut log = FaultLog::new();
+        assert!(log.is_progressing());
+        for panel in [Device::Display1, Device::Display2] {
+            for step in 0..OFFLINE_AFTER {
+                log.record(ClusterError::new(panel, Operation::Flush), false);
+                let escalating = step + 1 >= RECOVER_BUS_AFTER && step + 1 < OFFLINE_AFTER;
+                assert_eq!(log.is_progressing(), !escalating, "{:?} {}", panel, step);
+            }
+        }
+        // Both panels gone, ADCs still answering: carry on
+        assert!(log.is_progressing());
+
+        for adc in [Device::Adc1, Device::Adc2] {
+            for _ in 0..OFFLINE_AFTER {
+                log.record(ClusterError::new(adc, Operation::Read), false);
+            }
+        }
+        assert!(!log.is_progressing());
+    }
+
+    #[test]
+    fn success_starts_over() {
+        let mut log = FaultLog::new();
+        log.record(FLUSH, false);
+        log.record(FLUSH, false);
+        log.cleared(Device::Display2);
+        assert_eq!(log.consecutive(Device::Display2), 0);
+        assert_eq!(log.record(FLUSH, false), Recovery::Retry);
+        assert_eq!(log.total(Device::Display2), 3);
+    }
+
+    #[test]
+    fn devices_count_separately() {
+        let mut log = FaultLog::new();
+        log.record(FLUSH, false);
+        let read = ClusterError::new(Device::Adc1, Operation::Read);
+        assert_eq!(log.record(read, false), Recovery::Retry);
+        assert_eq!(log.total(Device::Adc1), 1);
+        assert_eq!(log.total(Device::Adc2), 0);
+        assert_eq!(alloc::format!("{}", read), "adc1 read");
+    }
+
+    #[test]
+    fn stuck_bus_skips_ahead() {
+        let mut log = FaultLog::new();
+        assert_eq!(log.record(FLUSH, true), Recovery::RecoverBus);
+        assert_eq!(log.bus_recoveries(), 1);
+        for _ in 0..4 {
+            log.record(FLUSH, true);
+        }
+        assert_eq!(log.record(FLUSH, true), Recovery::Offline);
+    }
+
+    #[test]
+    fn missing_devices_come_back() {
+        let mut log = FaultLog::new();
+        assert!(!log.retry_due(10_000));
+        for _ in 0..OFFLINE_AFTER {
+            log.record(FLUSH, false);
+        }
+        assert!(!log.retry_due(4_000));
+        assert!(log.retry_due(1_000));
+        assert!(!log.retry_due(1_000));
+
+        log.cleared(Device::Display2);
+        assert_eq!(log.status(), DeviceStatus::ALL_PRESENT);
+        assert_eq!(log.record(FLUSH, false), Recovery::Retry);
+    }
+
+    #[test]
+    fn reset_once_nothing_answers() {
+        let mut log = FaultLog::new();
+        let mut last = Recovery::Retry;
+        for device in Device::ALL {
+            for _ in 0..OFFLINE_AFTER {
+                last = log.record(ClusterError::new(device, Operation::Init), false);
+            }
+        }
+        assert_eq!(last, Recovery::Reset);
+    }
+
+    #[test]
+    fn panels_report_other_devices() {
+        let mut status = DeviceStatus::ALL_PRESENT;
+        assert_eq!(status.fault_for(Device::Display1), None);
+        status.present[Device::Display2 as usize] = false;
+        assert_eq!(status.fault_for(Device::Display1), Some(Device::Display2));
+        assert_eq!(status.fault_for(Device::Display2), None);
+        status.present[Device::Adc2 as usize] = false;
+        assert_eq!(status.fault_for(Device::Display1), Some(Device::Adc2));
+        assert_eq!(Device::Display2.fault_message(), "FUEL DISPLAY FAULT");
+    }
+}
@@ -0,0 +1,209 @@
+//! Fuel slosh damping.
+//!
+//! Fuel moves around the tank on every bump and corner, so the sender is
+//! only meaningful averaged over minutes. A plain long low-pass would take
+//! just as long to show a refuel or a fresh boot, so the filter runs with a
+//! short time constant for a while after boot and after a sustained rise.
+
+/// Tuning for [`SloshFilter`]. All times in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SloshConfig {
+    /// Time constant while driving
+    pub tau_ms: u32,
+    /// Time constant while settling (boot, refuel)
+    pub settle_tau_ms: u32,
+    /// How long a settle period lasts
+    pub settle_ms: u32,
+    /// Rise over the filtered level, in percent, that looks like a refuel...
+    pub refuel_step: f32,
+    /// ...once it has held this long
+    pub refuel_hold_ms: u32,
+}
+
+impl Default for SloshConfig {
+    fn default() -> Self {
+        Self {
+            tau_ms: 120_000,
+            settle_tau_ms: 1_000,
+            settle_ms: 5_000,
+            refuel_step: 10.0,
+            refuel_hold_ms: 15_000,
+        }
+    }
+}
+
+/// First-order low-pass with fast settle on boot and on refuel.
+#[derive(Clone, Debug)]
+pub struct SloshFilter {
+    config: SloshConfig,
+    value: Option<f32>,
+    settle_left_ms: u32,
+    rise_ms: u32,
+}
+
+impl SloshFilter {
+    pub const fn new(config: SloshConfig) -> Self {
+        Self {
+            config,
+            value: None,
+            settle_left_ms: 0,
+            rise_ms: 0,
+        }
+    }
+
+    pub fn config(&self) -> &SloshConfig {
+        &self.config
+    }
+
+    /// Filtered level, or `None` before the first sample.
+    pub fn value(&self) -> Option<f32> {
+        self.value
+    }
+
+    /// Starts a fast-settle period, e.g. when the user says they refuelled.
+    pub fn settle(&mut self) {
+        self.settle_left_ms = self.config.settle_ms;
+        self.rise_ms = 0;
+    }
+
+    /// Skips a sample that can't be trusted, e.g. from a broken sender
+    /// wire, keeping the level where it was. Any rise that looked like a
+    /// refuel starts over.
+    pub fn hold(&mut self) -> Option<f32> {
+        self.rise_ms = 0;
+        self.value
+    }
+
+    /// Feeds one sample taken `dt_ms` after the previous one.
+    pub fn update(&mut self, sample: f32, dt_ms: u32) -> f32 {
+        let Some(value) = self.value else {
+            // Boot: start from the first sample and settle around it.
+            self.value = Some(sample);
+            self.settle();
+            return sample;
+        };
+
+        if sample - value >= self.config.refuel_step {
+            self.rise_ms = self.rise_ms.saturating_add(dt_ms);
+            if self.rise_ms >= self.config.refuel_hold_ms {
+                self.settle();
+            }
+        } else {
+            self.rise_ms = 0;
+        }
+
+        let tau = if self.settle_left_ms > 0 {
+            self.config.settle_tau_ms
+        } else {
+            self.config.tau_ms
+        };
+        self.settle_left_ms = self.settle_left_ms.saturating_sub(dt_ms);
+
+        let alpha = dt_ms as f32 / (tau as f32 + dt_ms as f32);
+        let value = value + alpha * (sample - value);
+        self.value = Some(value);
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: u32 = 100;
+
+    /// Runs `sample` through the filter for `ms` worth of loop iterations.
+    fn run(filter: &mut SloshFilter, ms: u32, sample: impl Fn(u32) -> f32) -> f32 {
+        let mut out = filter.value().unwrap_or(0.0);
+        for i in 0..ms / DT {
+            out = filter.update(sample(i), DT);
+        }
+        out
+    }
+
+    #[test]
+    fn first_sample_is_taken_as_is() {
+        let mut filter = SloshFilter::new(SloshConfig::default());
+        assert_eq!(filter.value(), None);
+        assert_eq!(filter.update(62.0, DT), 62.0);
+    }
+
+    #[test]
+    fn settles_quickly_after_boot() {
+        let mut filter = SloshFilter::new(SloshConfig::default());
+        filter.update(0.0, DT); // sender still waking up
+        let out = run(&mut filter, 5_000, |_| 50.0);
+        assert!((out - 50.0).abs() < 1.0, "{}", out);
+    }
+
+    #[test]
+    fn rejects_slosh_while_driving() {
+        let mut filter = SloshFilter::new(SloshConfig::default());
+        run(&mut filter, 10_000, |_| 50.0);
+        // ±20% swings every couple of seconds for a minute
+        let out = run(&mut filter, 60_000, |i| {
+            if (i / 20) % 2 == 0 {
+                70.0
+            } else {
+                30.0
+            }
+        });
+        assert!((out - 50.0).abs() < 2.0, "{}", out);
+    }
+
+    #[test]
+    fn follows_slow_consumption() {
+        let mut filter = SloshFilter::new(SloshConfig::default());
+        run(&mut filter, 10_000, |_| 50.0);
+        let out = run(&mut filter, 600_000, |_| 40.0);
+        assert!((out - 40.0).abs() < 0.5, "{}", out);
+    }
+
+    #[test]
+    fn detects_refuel() {
+        let config = SloshConfig::default();
+        let mut filter = SloshFilter::new(config);
+        run(&mut filter, 10_000, |_| 20.0);
+        let out = run(
+            &mut filter,
+            config.refuel_hold_ms + config.settle_ms,
+            |_| 95.0,
+        );
+        assert!((out - 95.0).abs() < 2.0, "{}", out);
+    }
+
+    #[test]
+    fn brief_rise_is_not_a_refuel() {
+        let config = SloshConfig::default();
+        let mut filter = SloshFilter::new(config);
+        run(&mut filter, 10_000, |_| 20.0);
+        // Long uphill stretch, shorter than the refuel hold
+        let out = run(&mut filter, config.refuel_hold_ms - 1_000, |_| 60.0);
+        assert!(out < 30.0, "{}", out);
+    }
+
+    #[test]
+    fn holds_through_a_sender_fault() {
+        let mut filter = SloshFilter::new(SloshConfig::default());
+        run(&mut filter, 10_000, |_| 80.0);
+        // A minute of broken wire, where the sender would read empty
+        for _ in 0..60_000 / DT {
+            assert_eq!(filter.hold(), Some(80.0));
+        }
+        let out = run(&mut filter, 1_000, |_| 80.0);
+        assert!((out - 80.0).abs() < 0.01, "{}", out);
+
+        let mut filter = SloshFilter::new(SloshConfig::default());
+        assert_eq!(filter.hold(), None);
+        assert_eq!(filter.update(40.0, DT), 40.0);
+    }
+
+    #[test]
+    fn manual_settle() {
+        let mut filter = SloshFilter::new(SloshConfig::default());
+        run(&mut filter, 10_000, |_| 80.0);
+        filter.settle();
+        let out = run(&mut filter, 5_000, |_| 30.0);
+        assert!((out - 30.0).abs() < 1.0, "{}", out);
+    }
+}
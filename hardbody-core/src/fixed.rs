@@ -0,0 +1,454 @@
+//! Integer versions of the per-loop conversions in [`crate::sensors`].
+//!
+//! The M0+ has no FPU, so every `f32` divide and `ln` in the main loop is a
+//! software routine. [`FixedConversions`] does the float work once, when the
+//! settings are loaded or changed, and leaves each reading to integer
+//! multiplies, shifts and the RP2040's hardware divider; every divide is
+//! 32-bit, since a 64-bit one is a software routine again. Resistance is
+//! carried in 1/16 Ω and the thermistor goes through a table of °F against
+//! log₂ of its resistance, so no `ln` is taken per reading.
+//!
+//! Against the `f32` path, over every code from 0 to 32767:
+//! - fuel level is within 1 %
+//! - battery voltage is within 1 mV
+//! - coolant is within 0.5 °F between [`COOLANT_MIN_F`] and
+//!   [`COOLANT_MAX_F`], and saturates outside them
+
+use crate::{
+    curve::Curve,
+    sender::RATIO_MAX,
+    sensors::{
+        BatteryReading, Circuit, CoolantReading, FuelReading, MinMax, ADC_MAX, COOLANT_LIMITS,
+        FS_V, FUEL_CURVE_POINTS, FUEL_LIMITS, RAIL_WINDOW,
+    },
+    settings::Settings,
+    thermistor::Thermistor,
+};
+
+/// Coldest temperature the fixed-point coolant path reports.
+pub const COOLANT_MIN_F: f32 = -40.0;
+/// Hottest temperature the fixed-point coolant path reports.
+pub const COOLANT_MAX_F: f32 = 400.0;
+
+const CODE_MAX: i32 = ADC_MAX as i32;
+/// Fraction bits of resistances, so 16 per ohm.
+const OHMS_SHIFT: u32 = 4;
+
+/// `log2(1 + i/32)` in Q16, for `i` in `0..=32`.
+const LOG2_TABLE: [i32; 33] = [
+    0, 2909, 5732, 8473, 11136, 13727, 16248, 18704, 21098, 23433, 25711, 27936, 30109, 32234,
+    34312, 36346, 38336, 40286, 42196, 44068, 45904, 47705, 49472, 51207, 52911, 54584, 56229,
+    57845, 59434, 60997, 62534, 64047, 65536,
+];
+
+/// `log2(x)` in Q16, good to about 2e-4. `x` must not be zero.
+fn log2_q16(x: u32) -> i32 {
+    let whole = 31 - x.leading_zeros();
+    // Mantissa with the leading one shifted out: 5 bits of index, then 16
+    // of interpolation
+    let m = (x << x.leading_zeros()) << 1;
+    let i = (m >> 27) as usize;
+    let rem = ((m >> 11) & 0xFFFF) as i32;
+    let (lo, hi) = (LOG2_TABLE[i], LOG2_TABLE[i + 1]);
+    ((whole as i32) << 16) + lo + (((hi - lo) * rem) >> 16)
+}
+
+/// Codes clamped the way the `f32` path clamps them.
+fn clamp_codes(adc: i16, v33_adc: i16) -> (u32, u32) {
+    (
+        (adc as i32).clamp(0, CODE_MAX) as u32,
+        (v33_adc as i32).clamp(1, CODE_MAX) as u32,
+    )
+}
+
+/// `a · num / den` with only 32-bit divides, saturating. Exact while
+/// `a · (num % den)` fits in 32 bits; past that `den` loses low bits.
+fn mul_div(a: u32, num: u32, den: u32) -> u32 {
+    let (whole, rem) = (num / den, num % den);
+    let bits = |x: u32| 32 - x.leading_zeros();
+    let shift = (bits(a) + bits(den)).saturating_sub(32);
+    let part = a * (rem >> shift) / (den >> shift).max(1);
+    a.saturating_mul(whole).saturating_add(part)
+}
+
+/// Sender resistance in 1/16 Ω for a pull-up of `pull_q4`, saturating
+/// when the sender reads open.
+fn sender_ohms_q4(pull_q4: u32, adc: u32, v33: u32) -> u32 {
+    if adc >= v33 {
+        return u32::MAX;
+    }
+    // R = R_pull · code / (code_v33 − code)
+    mul_div(pull_q4, adc, v33 - adc)
+}
+
+/// `code / code_v33` through the hardware divider, clamped below 1 the
+/// way the `f32` path clamps it.
+fn ratio(code: u32, v33: u32) -> f32 {
+    // 15-bit codes, so the shift stays inside 32 bits
+    let ratio_q16 = (code.min(v33) << 16) / v33;
+    (ratio_q16 as f32 * (1.0 / 65536.0)).min(RATIO_MAX)
+}
+
+/// A [`Curve`] with its inputs in 1/16 Ω and outputs in Q8.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct FixedCurve<const N: usize> {
+    points: [(u32, i32); N],
+}
+
+impl<const N: usize> FixedCurve<N> {
+    fn new(curve: &Curve<N>) -> Self {
+        Self {
+            points: curve.points().map(|p| {
+                let x = (p.x.max(0.0) * (1 << OHMS_SHIFT) as f32 + 0.5) as u32;
+                let y = p.y * 256.0;
+                (x, (y + 0.5 * y.signum()) as i32)
+            }),
+        }
+    }
+
+    fn eval_q8(&self, x: u32) -> i32 {
+        let (first, last) = (self.points[0], self.points[N - 1]);
+        if x <= first.0 {
+            return first.1;
+        }
+        for pair in self.points.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            // Points that rounding merged are skipped: x > x0 here
+            if x <= x1 {
+                // x − x0 ≤ x1 − x0, so the step is never more than y1 − y0
+                let dy = mul_div((y1 - y0).unsigned_abs(), x - x0, x1 - x0) as i32;
+                return if y1 >= y0 { y0 + dy } else { y0 - dy };
+            }
+        }
+        last.1
+    }
+}
+
+/// Coolant table spans 2⁻⁴..2²⁸ Ω, eight steps per doubling.
+const COOLANT_LOG2_MIN: i32 = -4;
+const COOLANT_STEP_SHIFT: u32 = 13;
+const COOLANT_STEPS: usize = 256;
+
+/// °F in hundredths against log₂ of thermistor resistance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CoolantTable {
+    centi_f: [i32; COOLANT_STEPS + 1],
+}
+
+impl CoolantTable {
+    fn new(thermistor: &Thermistor) -> Self {
+        let sh = thermistor.steinhart_hart();
+        let mut centi_f = [0; COOLANT_STEPS + 1];
+        for (i, entry) in centi_f.iter_mut().enumerate() {
+            let log2 = COOLANT_LOG2_MIN as f32 + i as f32 / 8.0;
+            let l = log2 * core::f32::consts::LN_2;
+            let inv_t = sh.a + sh.b * l + sh.c * l * l * l;
+            // A curve fitted over the gauge's range can turn over far
+            // outside it; anything past 0 K counts as hot
+            let f = match inv_t > 0.0 {
+                true => (1.0 / inv_t - 273.15) * 1.8 + 32.0,
+                false => f32::MAX,
+            };
+            // Well past the reported range, so the clamp never bends the
+            // interpolation inside it
+            *entry = (f.clamp(-1_000.0, 2_000.0) * 100.0) as i32;
+        }
+        Self { centi_f }
+    }
+
+    /// Interpolated °F in hundredths for `log2` of the resistance in Q16.
+    fn eval(&self, log2_q16: i32) -> i32 {
+        let pos = log2_q16.saturating_sub(COOLANT_LOG2_MIN << 16);
+        if pos <= 0 {
+            return self.centi_f[0];
+        }
+        let i = (pos >> COOLANT_STEP_SHIFT) as usize;
+        if i >= COOLANT_STEPS {
+            return self.centi_f[COOLANT_STEPS];
+        }
+        let rem = (pos & ((1 << COOLANT_STEP_SHIFT) - 1)) as i64;
+        let (lo, hi) = (self.centi_f[i], self.centi_f[i + 1]);
+        lo + (((hi - lo) as i64 * rem) >> COOLANT_STEP_SHIFT) as i32
+    }
+}
+
+/// The fuel, battery and coolant conversions with the float work done up
+/// front. Rebuild it whenever the settings change.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FixedConversions {
+    pull_q4: u32,
+    log2_pull_q16: i32,
+    /// Pull-up tolerance in Q16
+    tolerance_q16: u32,
+    /// log₂ of the pull-up's low and high ends over its nominal, in Q16
+    log2_tolerance_q16: [i32; 2],
+    fuel: FixedCurve<FUEL_CURVE_POINTS>,
+    /// Millivolts per code in Q16, divider included
+    battery_mv_q16: u32,
+    coolant: CoolantTable,
+}
+
+impl FixedConversions {
+    pub fn new(settings: &Settings) -> Self {
+        let Circuit {
+            pull_up_ohms,
+            pull_up_tolerance,
+            battery_r1_ohms: r1,
+            battery_r2_ohms: r2,
+        } = settings.circuit;
+        let pull_q4 = ((pull_up_ohms * (1 << OHMS_SHIFT) as f32 + 0.5) as u32).max(1);
+        let mv_per_code = FS_V * 1000.0 / ADC_MAX * (r1 + r2) / r2;
+        let tolerance_q16 = (pull_up_tolerance.clamp(0.0, 0.99) * 65536.0 + 0.5) as u32;
+        Self {
+            pull_q4,
+            log2_pull_q16: log2_q16(pull_q4) - ((OHMS_SHIFT as i32) << 16),
+            tolerance_q16,
+            log2_tolerance_q16: [65536 - tolerance_q16, 65536 + tolerance_q16]
+                .map(|q16| log2_q16(q16) - (16 << 16)),
+            fuel: FixedCurve::new(&settings.fuel_curve),
+            battery_mv_q16: (mv_per_code * 65536.0 + 0.5) as u32,
+            coolant: CoolantTable::new(&settings.thermistor),
+        }
+    }
+
+    /// Tank level, 0..=100, as [`FuelReading::from_codes`] works it out.
+    pub fn fuel_percent(&self, adc: i16, v33_adc: i16) -> u8 {
+        let (adc, v33) = clamp_codes(adc, v33_adc);
+        let q8 = self.fuel_q8(sender_ohms_q4(self.pull_q4, adc, v33));
+        ((q8 + 128) >> 8) as u8
+    }
+
+    fn fuel_q8(&self, ohms_q4: u32) -> i32 {
+        self.fuel.eval_q8(ohms_q4).clamp(0, 100 << 8)
+    }
+
+    /// Battery voltage in millivolts.
+    pub fn battery_millivolts(&self, batt_adc: i16) -> u32 {
+        let code = (batt_adc as i32).clamp(0, CODE_MAX) as u64;
+        ((code * self.battery_mv_q16 as u64 + (1 << 15)) >> 16) as u32
+    }
+
+    /// Coolant temperature in tenths of a °F, limited to
+    /// [`COOLANT_MIN_F`]..=[`COOLANT_MAX_F`].
+    pub fn coolant_tenths_f(&self, adc: i16, v33_adc: i16) -> i32 {
+        self.coolant_tenths_at(adc, v33_adc, 0)
+    }
+
+    /// As [`Self::coolant_tenths_f`], with the pull-up `log2_scale_q16`
+    /// off its nominal value.
+    fn coolant_tenths_at(&self, adc: i16, v33_adc: i16, log2_scale_q16: i32) -> i32 {
+        let log2_pull_q16 = self.log2_pull_q16 + log2_scale_q16;
+        let (adc, v33) = clamp_codes(adc, v33_adc);
+        let centi_f = match (adc, v33 - adc.min(v33)) {
+            // Shorted: as hot as the table goes
+            (0, _) => self.coolant.eval(i32::MIN),
+            // Open: as cold as it goes
+            (_, 0) => self.coolant.eval(i32::MAX),
+            // log2 R = log2 R_pull + log2 code − log2 (code_v33 − code)
+            (adc, rest) => self
+                .coolant
+                .eval(log2_pull_q16 + log2_q16(adc) - log2_q16(rest)),
+        };
+        let (min, max) = ((COOLANT_MIN_F * 10.0) as i32, (COOLANT_MAX_F * 10.0) as i32);
+        ((centi_f + 5).div_euclid(10)).clamp(min, max)
+    }
+
+    /// [`FuelReading::from_codes`] without float division.
+    pub fn fuel(&self, adc: i16, v33_adc: i16) -> FuelReading {
+        let (code, v33) = clamp_codes(adc, v33_adc);
+        let ratio = ratio(code, v33);
+        // Sender ohms scale with the pull-up, so do its ends
+        let ohms_q4 = sender_ohms_q4(self.pull_q4, code, v33);
+        let spread_q4 = mul_div(ohms_q4, self.tolerance_q16, 1 << 16);
+        let percent = |ohms_q4| self.fuel_q8(ohms_q4) as f32 * (1.0 / 256.0);
+        let mut bounds = MinMax::new(percent(ohms_q4 - spread_q4));
+        bounds.update(percent(ohms_q4.saturating_add(spread_q4)));
+        FuelReading {
+            ratio,
+            ohms: self.ohms(code, v33),
+            percent: self.fuel_percent(adc, v33_adc),
+            bounds,
+            state: FUEL_LIMITS.classify(ratio, RAIL_WINDOW.classify(v33_adc)),
+        }
+    }
+
+    /// [`BatteryReading::from_code`] without float division.
+    pub fn battery(&self, batt_adc: i16) -> BatteryReading {
+        BatteryReading {
+            volts: self.battery_millivolts(batt_adc) as f32 * 0.001,
+        }
+    }
+
+    /// [`CoolantReading::from_codes`] without float division or `ln`.
+    pub fn coolant(&self, adc: i16, v33_adc: i16) -> CoolantReading {
+        let (code, v33) = clamp_codes(adc, v33_adc);
+        let [low, high] = self
+            .log2_tolerance_q16
+            .map(|scale| self.coolant_tenths_at(adc, v33_adc, scale) as f32 * 0.1);
+        let mut bounds = MinMax::new(low);
+        bounds.update(high);
+        CoolantReading {
+            ohms: self.ohms(code, v33),
+            fahrenheit: self.coolant_tenths_f(adc, v33_adc) as f32 * 0.1,
+            bounds,
+            state: COOLANT_LIMITS.classify(ratio(code, v33), RAIL_WINDOW.classify(v33_adc)),
+        }
+    }
+
+    fn ohms(&self, code: u32, v33: u32) -> f32 {
+        let q4 = sender_ohms_q4(self.pull_q4, code, v33);
+        q4 as f32 * (1.0 / (1 << OHMS_SHIFT) as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sensors::SenderState,
+        thermistor::{SteinhartHart, DEFAULT_THERMISTOR},
+    };
+
+    const V33: [i16; 3] = [24_000, 26_400, 32_767];
+
+    fn codes() -> impl Iterator<Item = i16> {
+        0..=i16::MAX
+    }
+
+    #[test]
+    fn mul_div_in_32_bits() {
+        assert_eq!(mul_div(16_000, 32_000, 767), 667_535);
+        assert_eq!(mul_div(25_600, 5, 11), 11_636);
+        // A pull-up of a few hundred kΩ still comes out within a part in 10⁴
+        let big = mul_div(3_200_000, 20_000, 6_400);
+        assert!(big.abs_diff(10_000_000) < 1_000, "{}", big);
+        assert_eq!(mul_div(u32::MAX, 3, 1), u32::MAX);
+    }
+
+    #[test]
+    fn log2_matches_float() {
+        for x in [1, 2, 3, 5, 1_000, 16_000, 26_399, 1 << 20, u32::MAX] {
+            let exact = (x as f64).log2() * 65536.0;
+            assert!((log2_q16(x) as f64 - exact).abs() < 16.0, "{}", x);
+        }
+    }
+
+    #[test]
+    fn fuel_within_one_percent() {
+        let settings = Settings::default();
+        let fixed = FixedConversions::new(&settings);
+        for v33 in V33 {
+            for code in codes() {
+                let float =
+                    FuelReading::from_codes(code, v33, &settings.fuel_curve, &settings.circuit)
+                        .percent;
+                let int = fixed.fuel_percent(code, v33);
+                assert!(
+                    float.abs_diff(int) <= 1,
+                    "{} / {}: {} {}",
+                    code,
+                    v33,
+                    float,
+                    int
+                );
+
+                // Full scale included: both stop short of 1
+                let float =
+                    FuelReading::from_codes(code, v33, &settings.fuel_curve, &settings.circuit);
+                let int = fixed.fuel(code, v33);
+                assert!(int.ratio <= RATIO_MAX, "{} / {}: {:?}", code, v33, int);
+                assert!(
+                    (float.ratio - int.ratio).abs() <= 1.0 / 65536.0,
+                    "{} / {}: {:?} {:?}",
+                    code,
+                    v33,
+                    float,
+                    int
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn battery_within_a_millivolt() {
+        let settings = Settings::default();
+        let fixed = FixedConversions::new(&settings);
+        for code in codes() {
+            let float = BatteryReading::from_code(code, &settings.circuit).volts * 1000.0;
+            let int = fixed.battery_millivolts(code) as f32;
+            assert!((float - int).abs() <= 1.0, "{}: {} {}", code, float, int);
+        }
+        assert_eq!(fixed.battery_millivolts(-100), 0);
+    }
+
+    fn assert_coolant_matches(settings: &Settings) {
+        let fixed = FixedConversions::new(settings);
+        for v33 in V33 {
+            for code in codes() {
+                let float =
+                    CoolantReading::from_codes(code, v33, &settings.thermistor, &settings.circuit)
+                        .fahrenheit
+                        .clamp(COOLANT_MIN_F, COOLANT_MAX_F);
+                let int = fixed.coolant_tenths_f(code, v33) as f32 / 10.0;
+                assert!(
+                    (float - int).abs() <= 0.5,
+                    "{} / {}: {} {}",
+                    code,
+                    v33,
+                    float,
+                    int
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn coolant_within_half_a_degree() {
+        assert_coolant_matches(&Settings::default());
+    }
+
+    #[test]
+    fn coolant_with_steinhart_hart_and_another_pull_up() {
+        let mut settings = Settings::default();
+        settings.circuit.pull_up_ohms = 2_200.0;
+        settings.thermistor = Thermistor::SteinhartHart(
+            SteinhartHart::from_points([(3_520.0, 32.0), (185.0, 160.0), (47.0, 240.0)]).unwrap(),
+        );
+        assert_coolant_matches(&settings);
+    }
+
+    #[test]
+    fn readings_keep_their_shape() {
+        let settings = Settings::default();
+        let fixed = FixedConversions::new(&settings);
+        let float =
+            CoolantReading::from_codes(6_000, 26_400, &DEFAULT_THERMISTOR, &settings.circuit);
+        let int = fixed.coolant(6_000, 26_400);
+        assert!((float.ohms - int.ohms).abs() < 0.1, "{:?} {:?}", float, int);
+        let fuel = fixed.fuel(1_200, 26_400);
+        assert!((fuel.ratio - 1_200.0 / 26_400.0).abs() < 1e-4, "{:?}", fuel);
+        assert_eq!(fixed.fuel(30_000, 26_400).ratio, RATIO_MAX);
+        assert_eq!(fixed.fuel(30_000, 26_400).state, SenderState::Open);
+        assert_eq!(fixed.coolant(0, 26_400).state, SenderState::Short);
+        assert_eq!(int.state, float.state);
+        assert!((int.bounds.min - float.bounds.min).abs() < 0.2, "{:?}", int);
+        assert!((int.bounds.max - float.bounds.max).abs() < 0.2, "{:?}", int);
+        assert!(int.bounds.min < int.fahrenheit && int.fahrenheit < int.bounds.max);
+
+        // Fuel is only within 1 % on either path
+        let float = FuelReading::from_codes(1_200, 26_400, &settings.fuel_curve, &settings.circuit);
+        let fuel = fixed.fuel(1_200, 26_400);
+        assert!(
+            (fuel.bounds.min - float.bounds.min).abs() < 1.0,
+            "{:?} {:?}",
+            fuel,
+            float
+        );
+        assert!(
+            (fuel.bounds.max - float.bounds.max).abs() < 1.0,
+            "{:?}",
+            fuel
+        );
+        assert_eq!(fixed.fuel(1_200, 24_000).state, SenderState::NoReference);
+    }
+}
@@ -0,0 +1,617 @@
+#![no_std]
+#[cfg(test)]
+extern crate alloc;
+
+use embedded_graphics::{
+    mono_font::{
+        ascii::{FONT_10X20, FONT_6X10},
+        MonoTextStyleBuilder,
+    },
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Line, PrimitiveStyle, Rectangle},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+};
+use micromath::F32Ext;
+
+/// Longest piece of text any screen formats.
+const LABEL_LEN: usize = 32;
+
+/// `format!` into a buffer on the stack. Anything past [`LABEL_LEN`] is
+/// dropped.
+macro_rules! label {
+    ($($arg:tt)*) => {{
+        let mut text = heapless::String::<LABEL_LEN>::new();
+        let _ = core::fmt::Write::write_fmt(&mut text, format_args!($($arg)*));
+        text
+    }};
+}
+
+pub mod acquire;
+pub mod console;
+pub mod crash;
+pub mod curve;
+pub mod fault;
+pub mod filter;
+pub mod fixed;
+pub mod odometer;
+pub mod pages;
+pub mod pulse;
+pub mod reset;
+pub mod sampling;
+pub mod sender;
+pub mod sensors;
+pub mod settings;
+pub mod speed;
+pub mod tach;
+pub mod thermistor;
+pub mod warnings;
+
+pub use acquire::{Acquisition, Input, InputConfig};
+pub use console::{Action, Context, LineEditor, LineError, RawCodes};
+pub use crash::{CrashRecord, CrashReport, CRASH_TEXT_LEN};
+pub use curve::{Curve, CurvePoint};
+pub use fault::{ClusterError, Device, DeviceStatus, FaultLog, Operation, Recovery};
+pub use filter::{SloshConfig, SloshFilter};
+pub use fixed::FixedConversions;
+pub use odometer::{Distance, Odometer, OdometerLog, PendingRecord, Trip, ODOMETER_RECORD_LEN};
+pub use pages::{Page, Pager};
+pub use pulse::PulseTimer;
+pub use reset::{ResetFlags, ResetLog, ResetReason};
+pub use sampling::{FilterConfig, Pipeline, Stage};
+pub use sender::{Linear, ResistiveSender, SenderReading, Transfer};
+pub use sensors::{
+    BatteryReading, Circuit, CoolantReading, FuelCurve, FuelReading, MinMax, OilPressureCurve,
+    OilPressureReading, RailState, RailWindow, ReferenceReading, SenderLimits, SenderState,
+    Snapshot, DEFAULT_CIRCUIT, DEFAULT_FUEL_CURVE, DEFAULT_OIL_CURVE, RAIL_WINDOW,
+};
+pub use settings::{PendingWrite, Settings, SettingsStore, Slot, RECORD_LEN, SETTINGS_VERSION};
+pub use speed::{SpeedConfig, SpeedReading, Speedometer};
+pub use tach::{TachConfig, TachReading, Tachometer};
+pub use thermistor::{SteinhartHart, Thermistor, DEFAULT_THERMISTOR};
+pub use warnings::{Alert, Alerts, WarningConfig, WarningEngine, WarningInputs};
+
+/// Gauge spans that depend on the engine rather than the sender.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GaugeConfig {
+    /// Coolant gauge left end, °F
+    pub temp_min_f: f32,
+    /// Coolant gauge right end, °F
+    pub temp_max_f: f32,
+}
+
+impl Default for GaugeConfig {
+    fn default() -> Self {
+        Self {
+            temp_min_f: 120.0,
+            temp_max_f: 270.0,
+        }
+    }
+}
+
+/// Fuel gauge plus battery voltage readout.
+pub fn draw_fuel_gauge<D>(
+    display: &mut D,
+    fuel: FuelReading,
+    battery: BatteryReading,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let pct = fuel.percent;
+
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On)
+        .build();
+
+    let start = Point::new(15, 5);
+    let end = Point::new(113, 5);
+    let bar_h = 6;
+    let tick_h = 8;
+    let ptr_top = start.y + bar_h + 4;
+    let ptr_len = 12;
+    let w = (end.x - start.x) as u32;
+
+    Rectangle::new(start, Size::new(w, bar_h as u32))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+
+    for i in 0..=4 {
+        let x = start.x + (w as i32 * i) / 4;
+        let t0 = Point::new(x, start.y - (tick_h / 2));
+        let t1 = Point::new(x, start.y + bar_h + (tick_h / 2));
+        Line::new(t0, t1)
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off, 2))
+            .draw(display)?;
+    }
+
+    // A broken sender gets no pointer rather than a made-up level
+    if fuel.state.is_ok() {
+        let x_pos = start.x + ((pct as u32 * w) / 100) as i32;
+        Line::new(
+            Point::new(x_pos, ptr_top),
+            Point::new(x_pos, ptr_top + ptr_len),
+        )
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 4))
+        .draw(display)?;
+    }
+
+    Text::with_baseline("E", Point::new(0, 2), text_style, Baseline::Top).draw(display)?;
+    Text::with_baseline("F", Point::new(118, 2), text_style, Baseline::Top).draw(display)?;
+    Text::with_baseline(
+        &match fuel.state {
+            SenderState::Ok => label!("{}%", pct),
+            state => label!("{}", state.label()),
+        },
+        Point::new(44, 30),
+        text_style,
+        Baseline::Top,
+    )
+    .draw(display)?;
+
+    Text::with_baseline(
+        &label!("{:.2}V", battery.volts),
+        Point::new(44, 45),
+        text_style,
+        Baseline::Top,
+    )
+    .draw(display)?;
+
+    Ok(())
+}
+
+/// Coolant temperature gauge, with the lowest and highest seen since
+/// power-on underneath once there are any.
+pub fn draw_temp_gauge<D>(
+    display: &mut D,
+    coolant: CoolantReading,
+    range: Option<MinMax>,
+    gauges: &GaugeConfig,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let (min_f, max_f) = (gauges.temp_min_f, gauges.temp_max_f);
+    let t_f = coolant.fahrenheit;
+
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On)
+        .build();
+
+    let start = Point::new(15, 5);
+    let end = Point::new(113, 5);
+    let bar_h = 6;
+    let ptr_top = start.y + bar_h + 4;
+    let ptr_len = 12;
+    let w = (end.x - start.x) as u32;
+
+    Rectangle::new(start, Size::new(w, bar_h as u32))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+
+    if coolant.state.is_ok() {
+        let pct = ((t_f - min_f) / (max_f - min_f)).clamp(0.0, 1.0);
+        let x_pos = start.x + (F32Ext::round(pct * w as f32) as i32);
+        Line::new(
+            Point::new(x_pos, ptr_top),
+            Point::new(x_pos, ptr_top + ptr_len),
+        )
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 4))
+        .draw(display)?;
+    }
+
+    let bar_thickness = 6;
+    let tick_height = 8;
+
+    let x = 30;
+    Line::new(
+        Point::new(x, start.y - (tick_height / 2)),
+        Point::new(x, start.y + bar_thickness + (tick_height / 2)),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off, 3))
+    .draw(display)?;
+
+    let x = 98;
+    Line::new(
+        Point::new(x, start.y - (tick_height / 2)),
+        Point::new(x, start.y + bar_thickness + (tick_height / 2)),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off, 3))
+    .draw(display)?;
+
+    Text::with_baseline("C", Point::new(0, 2), text_style, Baseline::Top).draw(display)?;
+    Text::with_baseline("H", Point::new(118, 2), text_style, Baseline::Top).draw(display)?;
+    Text::with_baseline(
+        &match coolant.state {
+            SenderState::Ok => label!("{:.0}F", t_f),
+            state => label!("{}", state.label()),
+        },
+        Point::new(44, 30),
+        text_style,
+        Baseline::Top,
+    )
+    .draw(display)?;
+
+    if let Some(range) = range {
+        Text::with_baseline(
+            &label!("{:.0}/{:.0}F", range.min, range.max),
+            Point::new(44, 45),
+            text_style,
+            Baseline::Top,
+        )
+        .draw(display)?;
+    }
+
+    Ok(())
+}
+
+/// Oil pressure gauge, 0..80 psi.
+pub fn draw_oil_pressure_gauge<D>(display: &mut D, oil: OilPressureReading) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    // Gauge span
+    const MAX_PSI: f32 = 80.0;
+
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On)
+        .build();
+
+    let start = Point::new(15, 5);
+    let end = Point::new(113, 5);
+    let bar_h = 6;
+    let tick_h = 8;
+    let ptr_top = start.y + bar_h + 4;
+    let ptr_len = 12;
+    let w = (end.x - start.x) as u32;
+
+    Rectangle::new(start, Size::new(w, bar_h as u32))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+
+    // One tick per 20 psi
+    for i in 0..=4 {
+        let x = start.x + (w as i32 * i) / 4;
+        let t0 = Point::new(x, start.y - (tick_h / 2));
+        let t1 = Point::new(x, start.y + bar_h + (tick_h / 2));
+        Line::new(t0, t1)
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off, 2))
+            .draw(display)?;
+    }
+
+    if oil.state.is_ok() {
+        let pct = (oil.psi / MAX_PSI).clamp(0.0, 1.0);
+        let x_pos = start.x + (F32Ext::round(pct * w as f32) as i32);
+        Line::new(
+            Point::new(x_pos, ptr_top),
+            Point::new(x_pos, ptr_top + ptr_len),
+        )
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 4))
+        .draw(display)?;
+    }
+
+    Text::with_baseline("L", Point::new(0, 2), text_style, Baseline::Top).draw(display)?;
+    Text::with_baseline("H", Point::new(118, 2), text_style, Baseline::Top).draw(display)?;
+    Text::with_baseline(
+        &match oil.state {
+            SenderState::Ok => label!("{:.0}PSI", oil.psi.max(0.0)),
+            state => label!("{}", state.label()),
+        },
+        Point::new(44, 30),
+        text_style,
+        Baseline::Top,
+    )
+    .draw(display)?;
+
+    Text::with_baseline("OIL", Point::new(44, 45), text_style, Baseline::Top).draw(display)?;
+
+    Ok(())
+}
+
+/// Tachometer, 0..6000 rpm with a tick every 1000.
+pub fn draw_tach_gauge<D>(display: &mut D, tach: TachReading) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    // Gauge span
+    const MAX_RPM: f32 = 6_000.0;
+
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On)
+        .build();
+
+    let start = Point::new(15, 5);
+    let end = Point::new(113, 5);
+    let bar_h = 6;
+    let tick_h = 8;
+    let ptr_top = start.y + bar_h + 4;
+    let ptr_len = 12;
+    let w = (end.x - start.x) as u32;
+
+    Rectangle::new(start, Size::new(w, bar_h as u32))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+
+    for i in 0..=6 {
+        let x = start.x + (w as i32 * i) / 6;
+        let t0 = Point::new(x, start.y - (tick_h / 2));
+        let t1 = Point::new(x, start.y + bar_h + (tick_h / 2));
+        Line::new(t0, t1)
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off, 2))
+            .draw(display)?;
+    }
+
+    let pct = (tach.rpm / MAX_RPM).clamp(0.0, 1.0);
+    let x_pos = start.x + (F32Ext::round(pct * w as f32) as i32);
+    Line::new(
+        Point::new(x_pos, ptr_top),
+        Point::new(x_pos, ptr_top + ptr_len),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 4))
+    .draw(display)?;
+
+    // Readout steps in 50s so it doesn't flicker at idle
+    let rpm = (F32Ext::round(tach.rpm.max(0.0) / 50.0) * 50.0) as u32;
+
+    Text::with_baseline("0", Point::new(0, 2), text_style, Baseline::Top).draw(display)?;
+    Text::with_baseline("6", Point::new(118, 2), text_style, Baseline::Top).draw(display)?;
+    Text::with_baseline(
+        &label!("{}", rpm),
+        Point::new(44, 30),
+        text_style,
+        Baseline::Top,
+    )
+    .draw(display)?;
+
+    Text::with_baseline("RPM", Point::new(44, 45), text_style, Baseline::Top).draw(display)?;
+
+    Ok(())
+}
+
+/// Speedometer, 0..100 mph, with the total odometer underneath.
+pub fn draw_speed_gauge<D>(
+    display: &mut D,
+    speed: SpeedReading,
+    odometer: &Odometer,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    // Gauge span
+    const MAX_MPH: f32 = 100.0;
+
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On)
+        .build();
+
+    let start = Point::new(15, 5);
+    let end = Point::new(113, 5);
+    let bar_h = 6;
+    let tick_h = 8;
+    let ptr_top = start.y + bar_h + 4;
+    let ptr_len = 12;
+    let w = (end.x - start.x) as u32;
+
+    Rectangle::new(start, Size::new(w, bar_h as u32))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+
+    // One tick per 20 mph
+    for i in 0..=5 {
+        let x = start.x + (w as i32 * i) / 5;
+        let t0 = Point::new(x, start.y - (tick_h / 2));
+        let t1 = Point::new(x, start.y + bar_h + (tick_h / 2));
+        Line::new(t0, t1)
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off, 2))
+            .draw(display)?;
+    }
+
+    let pct = (speed.mph / MAX_MPH).clamp(0.0, 1.0);
+    let x_pos = start.x + (F32Ext::round(pct * w as f32) as i32);
+    Line::new(
+        Point::new(x_pos, ptr_top),
+        Point::new(x_pos, ptr_top + ptr_len),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 4))
+    .draw(display)?;
+
+    Text::with_baseline("0", Point::new(0, 2), text_style, Baseline::Top).draw(display)?;
+    Text::with_baseline("1", Point::new(118, 2), text_style, Baseline::Top).draw(display)?;
+    Text::with_baseline(
+        &label!("{:.0}MPH", speed.mph.max(0.0)),
+        Point::new(44, 30),
+        text_style,
+        Baseline::Top,
+    )
+    .draw(display)?;
+
+    Text::with_baseline(
+        &label!("{}", odometer.total()),
+        Point::new(44, 45),
+        text_style,
+        Baseline::Top,
+    )
+    .draw(display)?;
+
+    Ok(())
+}
+
+/// Total and both trip meters, labelled on the left and right-aligned.
+pub fn draw_trip_page<D>(display: &mut D, odometer: &Odometer) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On)
+        .build();
+    let right = TextStyleBuilder::new()
+        .alignment(Alignment::Right)
+        .baseline(Baseline::Top)
+        .build();
+
+    let rows = [
+        ("ODO", odometer.total(), 2),
+        ("A", odometer.trip(Trip::A), 23),
+        ("B", odometer.trip(Trip::B), 44),
+    ];
+    for (label, distance, y) in rows {
+        Text::with_baseline(label, Point::new(0, y), text_style, Baseline::Top).draw(display)?;
+        Text::with_text_style(
+            &label!("{}", distance),
+            Point::new(127, y),
+            text_style,
+            right,
+        )
+        .draw(display)?;
+    }
+
+    Ok(())
+}
+
+/// Draws whichever gauge `page` is from one frame's readings.
+pub fn draw_page<D>(
+    display: &mut D,
+    page: Page,
+    snapshot: &Snapshot,
+    gauges: &GaugeConfig,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    match page {
+        Page::Coolant => draw_temp_gauge(display, snapshot.coolant, snapshot.coolant_range, gauges),
+        Page::Fuel => draw_fuel_gauge(display, snapshot.fuel, snapshot.battery),
+        Page::OilPressure => draw_oil_pressure_gauge(display, snapshot.oil),
+        Page::Tach => draw_tach_gauge(display, snapshot.tach),
+        Page::Speed => draw_speed_gauge(display, snapshot.speed, &snapshot.odometer),
+        Page::Trip => draw_trip_page(display, &snapshot.odometer),
+    }
+}
+
+/// Replaces the second text line of a gauge with an alert message.
+///
+/// Draw the gauge first; flashing is left to the caller (e.g. toggling the
+/// panel's hardware invert) so the text stays legible in both phases.
+pub fn draw_warning<D>(display: &mut D, alert: Alert) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On)
+        .build();
+
+    Rectangle::new(Point::new(0, 46), Size::new(128, 18))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+        .draw(display)?;
+
+    Text::with_text_style(
+        alert.message(),
+        Point::new(64, 45),
+        text_style,
+        TextStyleBuilder::new()
+            .alignment(Alignment::Center)
+            .baseline(Baseline::Top)
+            .build(),
+    )
+    .draw(display)?;
+
+    Ok(())
+}
+
+/// Replaces the second text line of a gauge with a missing-device notice.
+///
+/// Uses the small font so the longest message fits the panel width.
+pub fn draw_fault<D>(display: &mut D, missing: Device) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(BinaryColor::On)
+        .build();
+
+    Rectangle::new(Point::new(0, 46), Size::new(128, 18))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+        .draw(display)?;
+
+    Text::with_text_style(
+        missing.fault_message(),
+        Point::new(64, 50),
+        text_style,
+        TextStyleBuilder::new()
+            .alignment(Alignment::Center)
+            .baseline(Baseline::Top)
+            .build(),
+    )
+    .draw(display)?;
+
+    Ok(())
+}
+
+/// Shown for a moment at boot: why the last reset happened and how many
+/// watchdog and software resets there have been since power-on.
+pub fn draw_boot_screen<D>(display: &mut D, resets: &ResetLog) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On)
+        .build();
+    let centered = TextStyleBuilder::new()
+        .alignment(Alignment::Center)
+        .baseline(Baseline::Top)
+        .build();
+
+    let counts = label!("WD {} SW {}", resets.watchdog, resets.software);
+    for (text, y) in [
+        ("RESET", 2),
+        (resets.reason.label(), 23),
+        (counts.as_str(), 44),
+    ] {
+        Text::with_text_style(text, Point::new(64, y), text_style, centered).draw(display)?;
+    }
+
+    Ok(())
+}
+
+/// Characters per line in `FONT_6X10`.
+const SMALL_COLUMNS: usize = 21;
+
+/// A panic message from before the last reset, wrapped in the small font.
+pub fn draw_crash_report<D>(display: &mut D, text: &str) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(BinaryColor::On)
+        .build();
+
+    let mut y = 2;
+    for paragraph in text.lines() {
+        let mut rest = paragraph;
+        loop {
+            let split = rest
+                .char_indices()
+                .nth(SMALL_COLUMNS)
+                .map_or(rest.len(), |(i, _)| i);
+            let (line, tail) = rest.split_at(split);
+            Text::with_baseline(line, Point::new(1, y), text_style, Baseline::Top).draw(display)?;
+            y += 10;
+            rest = tail;
+            if rest.is_empty() {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
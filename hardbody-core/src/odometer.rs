@@ -0,0 +1,441 @@
+//! Total and trip distance, counted in whole VSS pulses.
+//!
+//! Each counter keeps tenths of a mile plus the leftover fraction as an
+//! integer remainder, so adding pulses one at a time or a thousand at once
+//! lands on exactly the same reading, however long the truck runs.
+//!
+//! Both survive a power cut, remainders and all, in an [`OdometerLog`]:
+//! small records appended one after another across two flash sectors, so a
+//! sector is only erased once it has filled up rather than on every save.
+
+use core::fmt;
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use crate::settings::{is_newer, Slot};
+
+/// Distance in tenths of a mile, plus the part of a tenth not yet shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Distance {
+    tenths: u32,
+    /// Fraction of a tenth, in units of `1 / pulses_per_mile` tenths
+    remainder: u32,
+}
+
+impl Distance {
+    pub const ZERO: Self = Self::from_tenths(0);
+
+    pub const fn from_tenths(tenths: u32) -> Self {
+        Self {
+            tenths,
+            remainder: 0,
+        }
+    }
+
+    pub fn tenths(self) -> u32 {
+        self.tenths
+    }
+
+    fn add_pulses(&mut self, pulses: u32, pulses_per_mile: u32) {
+        if pulses_per_mile == 0 {
+            return;
+        }
+        // A pulse is 10 / pulses_per_mile tenths.
+        let ppm = pulses_per_mile as u64;
+        let scaled = self.remainder as u64 + pulses as u64 * 10;
+        let tenths = self.tenths as u64 + scaled / ppm;
+        self.tenths = tenths.min(u32::MAX as u64) as u32;
+        self.remainder = (scaled % ppm) as u32;
+    }
+}
+
+/// Miles to one decimal place, e.g. `1234.5`.
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.tenths / 10, self.tenths % 10)
+    }
+}
+
+/// The two resettable trip meters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trip {
+    A,
+    B,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Odometer {
+    total: Distance,
+    trips: [Distance; 2],
+}
+
+impl Odometer {
+    /// Resumes from a saved total, with both trips at zero.
+    pub const fn new(total: Distance) -> Self {
+        Self {
+            total,
+            trips: [Distance::ZERO; 2],
+        }
+    }
+
+    pub fn total(&self) -> Distance {
+        self.total
+    }
+
+    pub fn trip(&self, trip: Trip) -> Distance {
+        self.trips[trip as usize]
+    }
+
+    pub fn reset_trip(&mut self, trip: Trip) {
+        self.trips[trip as usize] = Distance::ZERO;
+    }
+
+    /// Adds `pulses` VSS pulses at the current calibration.
+    pub fn add_pulses(&mut self, pulses: u32, pulses_per_mile: u32) {
+        self.total.add_pulses(pulses, pulses_per_mile);
+        for trip in &mut self.trips {
+            trip.add_pulses(pulses, pulses_per_mile);
+        }
+    }
+}
+
+/// Shortest gap between two saves while driving. At highway speed a tenth
+/// goes by every few seconds; this keeps one sector erase to an hour or so
+/// on the road.
+pub const SAVE_INTERVAL_MS: u32 = 60_000;
+
+/// Shortest gap between saves once the truck has stopped or the power is
+/// going. Key-off cuts the power outright, so the distance has to be in
+/// flash by then; the gap only stops a flickering rail from wearing it.
+pub const URGENT_SAVE_MS: u32 = 1_000;
+
+const MAGIC: [u8; 4] = *b"HBOD";
+/// Magic, sequence, then tenths and remainder of the total, trip A, trip B
+const BODY_LEN: usize = 4 + 4 + 8 * 3;
+
+/// Bytes one odometer record takes. A power of two, so a record never
+/// straddles a flash page; the bytes between the body and the CRC are left
+/// erased.
+pub const ODOMETER_RECORD_LEN: usize = 64;
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+impl Odometer {
+    pub fn encode(&self, sequence: u32) -> [u8; ODOMETER_RECORD_LEN] {
+        let mut record = [0xFF; ODOMETER_RECORD_LEN];
+        let [total, a, b] = [self.total, self.trips[0], self.trips[1]];
+        let words = [
+            sequence,
+            total.tenths,
+            total.remainder,
+            a.tenths,
+            a.remainder,
+            b.tenths,
+            b.remainder,
+        ];
+        record[..4].copy_from_slice(&MAGIC);
+        for (chunk, word) in record[4..BODY_LEN].chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        let crc_at = ODOMETER_RECORD_LEN - 4;
+        let crc = CRC32.checksum(&record[..crc_at]);
+        record[crc_at..].copy_from_slice(&crc.to_le_bytes());
+        record
+    }
+
+    /// `None` for erased flash, a torn write or a bad CRC.
+    pub fn decode(record: &[u8]) -> Option<(u32, Self)> {
+        let record = record.get(..ODOMETER_RECORD_LEN)?;
+        let (body, crc) = record.split_at(ODOMETER_RECORD_LEN - 4);
+        if CRC32.checksum(body).to_le_bytes() != crc || body[..4] != MAGIC {
+            return None;
+        }
+        let word = |i: usize| {
+            let at = 4 + i * 4;
+            u32::from_le_bytes([body[at], body[at + 1], body[at + 2], body[at + 3]])
+        };
+        let distance = |i: usize| Distance {
+            tenths: word(i),
+            remainder: word(i + 1),
+        };
+        let odometer = Self {
+            total: distance(1),
+            trips: [distance(3), distance(5)],
+        };
+        Some((word(0), odometer))
+    }
+}
+
+/// An odometer record to program, from [`OdometerLog::prepare`].
+pub struct PendingRecord {
+    pub slot: Slot,
+    /// Byte offset of the record within its sector
+    pub offset: usize,
+    /// Erase the sector first: it's the start of a sector, or one whose
+    /// contents are unknown
+    pub erase: bool,
+    pub sequence: u32,
+    pub odometer: Odometer,
+    pub record: [u8; ODOMETER_RECORD_LEN],
+}
+
+/// Where the newest odometer record is, and where the next one goes.
+///
+/// Records fill sector A one after another, then B, then A again. Like
+/// [`crate::SettingsStore`] this only sees byte slices; the firmware owns
+/// the sectors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OdometerLog {
+    /// Records that fit in one sector
+    capacity: usize,
+    newest: Option<(u32, Odometer)>,
+    next: (Slot, usize, bool),
+}
+
+impl OdometerLog {
+    /// Scans both sectors for the newest good record. With none, the
+    /// odometer starts from zero.
+    pub fn load(sector_a: &[u8], sector_b: &[u8]) -> (Self, Odometer) {
+        let capacity = sector_a.len().min(sector_b.len()) / ODOMETER_RECORD_LEN;
+        let sectors = [(Slot::A, sector_a), (Slot::B, sector_b)];
+
+        let mut newest: Option<(Slot, usize, u32, Odometer)> = None;
+        for (slot, sector) in sectors {
+            for (index, record) in sector.chunks_exact(ODOMETER_RECORD_LEN).enumerate() {
+                if let Some((seq, odometer)) = Odometer::decode(record) {
+                    if newest.is_none_or(|n| is_newer(seq, n.2)) {
+                        newest = Some((slot, index, seq, odometer));
+                    }
+                }
+            }
+        }
+
+        let next = match newest {
+            // The first untouched record after the newest one; anything
+            // in between is a torn write
+            Some((slot, index, _, _)) => {
+                let sector = sectors[slot as usize].1;
+                (index + 1..capacity)
+                    .find(|&i| is_erased(&sector[i * ODOMETER_RECORD_LEN..][..ODOMETER_RECORD_LEN]))
+                    .map_or((slot.other(), 0, true), |i| (slot, i, false))
+            }
+            None => (Slot::A, 0, true),
+        };
+        let log = Self {
+            capacity,
+            newest: newest.map(|(_, _, seq, odometer)| (seq, odometer)),
+            next,
+        };
+        (log, log.newest.map_or(Odometer::default(), |n| n.1))
+    }
+
+    /// The distances in the newest good record.
+    pub fn last_saved(&self) -> Option<Odometer> {
+        self.newest.map(|(_, odometer)| odometer)
+    }
+
+    /// Whether `odometer` has moved on from the last record and it has
+    /// been at least [`SAVE_INTERVAL_MS`] since that was written, or
+    /// [`URGENT_SAVE_MS`] when `urgent`: stopped, or the power failing.
+    pub fn due(&self, odometer: &Odometer, since_save_ms: u32, urgent: bool) -> bool {
+        let interval = match urgent {
+            true => URGENT_SAVE_MS,
+            false => SAVE_INTERVAL_MS,
+        };
+        self.last_saved() != Some(*odometer) && since_save_ms >= interval
+    }
+
+    /// Encodes `odometer` for the next free place in the log.
+    ///
+    /// Program and verify it, then call [`Self::commit`]. If the read-back
+    /// fails, [`Self::load`] again: the half-written record is skipped.
+    pub fn prepare(&self, odometer: &Odometer) -> PendingRecord {
+        let (slot, index, erase) = self.next;
+        let sequence = self.newest.map_or(0, |(seq, _)| seq.wrapping_add(1));
+        PendingRecord {
+            slot,
+            offset: index * ODOMETER_RECORD_LEN,
+            erase,
+            sequence,
+            odometer: *odometer,
+            record: odometer.encode(sequence),
+        }
+    }
+
+    pub fn commit(&mut self, written: &PendingRecord) {
+        self.newest = Some((written.sequence, written.odometer));
+        let index = written.offset / ODOMETER_RECORD_LEN + 1;
+        self.next = match index < self.capacity {
+            true => (written.slot, index, false),
+            false => (written.slot.other(), 0, true),
+        };
+    }
+}
+
+fn is_erased(bytes: &[u8]) -> bool {
+    bytes.iter().all(|&b| b == 0xFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    /// Four records to a sector, to reach the wrap quickly.
+    const SECTOR: usize = 4 * ODOMETER_RECORD_LEN;
+
+    /// Some way down the road, with a part-tenth on every counter.
+    fn driven(tenths: u32) -> Odometer {
+        let mut odo = Odometer::new(Distance::from_tenths(1_000));
+        odo.add_pulses(tenths * 3_756 / 10 + 7, 3_756);
+        odo.reset_trip(Trip::B);
+        odo.add_pulses(11, 3_756);
+        odo
+    }
+
+    /// Programs the way NOR flash does: erase sets every bit, programming
+    /// only clears them.
+    fn write(sectors: &mut [[u8; SECTOR]; 2], pending: &PendingRecord) {
+        let sector = &mut sectors[pending.slot as usize];
+        if pending.erase {
+            *sector = [0xFF; SECTOR];
+        }
+        for (byte, new) in sector[pending.offset..].iter_mut().zip(pending.record) {
+            *byte &= new;
+        }
+    }
+
+    #[test]
+    fn one_pulse_at_a_time_is_exact() {
+        let mut odo = Odometer::default();
+        // 4001 ppm doesn't divide a tenth evenly
+        for _ in 0..4_001 * 250 {
+            odo.add_pulses(1, 4_001);
+        }
+        assert_eq!(odo.total().tenths(), 2_500);
+        assert_eq!(odo.total().remainder, 0);
+    }
+
+    #[test]
+    fn chunking_does_not_matter() {
+        let mut a = Odometer::default();
+        let mut b = Odometer::default();
+        let mut pulses = 0;
+        for chunk in (1..500).cycle().take(5_000) {
+            a.add_pulses(chunk, 3_756);
+            pulses += chunk;
+        }
+        b.add_pulses(pulses, 3_756);
+        assert_eq!(a, b);
+        assert_eq!(a.total().tenths(), pulses * 10 / 3_756);
+    }
+
+    #[test]
+    fn trips_reset_independently() {
+        let mut odo = Odometer::new(Distance::from_tenths(1_234_567));
+        odo.add_pulses(4_000 * 12, 4_000);
+        odo.reset_trip(Trip::A);
+        odo.add_pulses(2_000, 4_000);
+
+        assert_eq!(odo.trip(Trip::A).tenths(), 5);
+        assert_eq!(odo.trip(Trip::B).tenths(), 125);
+        assert_eq!(odo.total().tenths(), 1_234_692);
+    }
+
+    #[test]
+    fn record_round_trips() {
+        let record = driven(123_456).encode(9);
+        assert_eq!(Odometer::decode(&record), Some((9, driven(123_456))));
+        assert_eq!(Odometer::decode(&[0xFF; ODOMETER_RECORD_LEN]), None);
+    }
+
+    #[test]
+    fn power_cycle_loses_nothing() {
+        let mut before = driven(250);
+        let (_, mut after) = Odometer::decode(&before.encode(0)).unwrap();
+        // Part-tenths from before the cut still add up afterwards
+        before.add_pulses(3_000, 3_756);
+        after.add_pulses(3_000, 3_756);
+        assert_eq!(after, before);
+        assert_ne!(after.trip(Trip::A), after.trip(Trip::B));
+    }
+
+    #[test]
+    fn erased_flash_starts_from_zero() {
+        let (log, loaded) = OdometerLog::load(&[0xFF; SECTOR], &[0xFF; SECTOR]);
+        assert_eq!(loaded, Odometer::default());
+        let pending = log.prepare(&driven(1));
+        assert_eq!(
+            (pending.slot, pending.offset, pending.erase),
+            (Slot::A, 0, true)
+        );
+    }
+
+    #[test]
+    fn appends_then_moves_to_the_other_sector() {
+        let mut sectors = [[0xFF; SECTOR]; 2];
+        let (mut log, _) = OdometerLog::load(&sectors[0], &sectors[1]);
+        let mut erases = 0;
+        for total in 1..=10 {
+            let pending = log.prepare(&driven(total));
+            erases += pending.erase as u32;
+            write(&mut sectors, &pending);
+            log.commit(&pending);
+
+            let (reloaded, loaded) = OdometerLog::load(&sectors[0], &sectors[1]);
+            assert_eq!(loaded, driven(total));
+            assert_eq!(reloaded, log);
+        }
+        // A at 1, B at 5, A again at 9
+        assert_eq!(erases, 3);
+    }
+
+    #[test]
+    fn torn_record_is_skipped() {
+        let mut sectors = [[0xFF; SECTOR]; 2];
+        let (mut log, _) = OdometerLog::load(&sectors[0], &sectors[1]);
+        let pending = log.prepare(&driven(7));
+        write(&mut sectors, &pending);
+        log.commit(&pending);
+
+        // Power lost half way through the next record
+        let mut torn = log.prepare(&driven(8));
+        torn.record[ODOMETER_RECORD_LEN / 2..].fill(0xFF);
+        write(&mut sectors, &torn);
+
+        let (log, loaded) = OdometerLog::load(&sectors[0], &sectors[1]);
+        assert_eq!(loaded, driven(7));
+        let retry = log.prepare(&driven(8));
+        assert_eq!(
+            (retry.slot, retry.offset),
+            (Slot::A, 2 * ODOMETER_RECORD_LEN)
+        );
+        assert_eq!(retry.sequence, 1);
+    }
+
+    #[test]
+    fn saves_are_rate_limited() {
+        let (log, _) = OdometerLog::load(&driven(5).encode(0), &[0xFF; ODOMETER_RECORD_LEN]);
+        assert!(!log.due(&driven(5), SAVE_INTERVAL_MS, false));
+        assert!(!log.due(&driven(6), SAVE_INTERVAL_MS - 1, false));
+        assert!(log.due(&driven(6), SAVE_INTERVAL_MS, false));
+    }
+
+    #[test]
+    fn stopping_saves_straight_away() {
+        let (log, _) = OdometerLog::load(&driven(5).encode(0), &[0xFF; ODOMETER_RECORD_LEN]);
+        // A pulse short of the next tenth still counts
+        let mut parked = driven(5);
+        parked.add_pulses(1, 3_756);
+        assert!(!log.due(&parked, URGENT_SAVE_MS - 1, true));
+        assert!(log.due(&parked, URGENT_SAVE_MS, true));
+        // ...but once it's in flash, sitting still writes nothing more
+        assert!(!log.due(&driven(5), SAVE_INTERVAL_MS, true));
+    }
+
+    #[test]
+    fn displays_tenths() {
+        assert_eq!(format!("{}", Distance::from_tenths(0)), "0.0");
+        assert_eq!(format!("{}", Distance::from_tenths(1_234_567)), "123456.7");
+    }
+}
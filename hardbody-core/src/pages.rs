@@ -0,0 +1,112 @@
+//! Which screen each OLED shows.
+//!
+//! There are more gauges than panels, so a panel can own several pages and
+//! rotate through them. An active alert jumps its panel straight to the
+//! page it's about and holds it there.
+
+use crate::warnings::Alerts;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Page {
+    Coolant,
+    Fuel,
+    OilPressure,
+    Tach,
+    Speed,
+    Trip,
+}
+
+/// Rotation for one panel.
+#[derive(Clone, Debug)]
+pub struct Pager {
+    pages: &'static [Page],
+    dwell_ms: u32,
+    index: usize,
+    elapsed_ms: u32,
+}
+
+impl Pager {
+    /// `pages` must not be empty; each is shown for `dwell_ms`.
+    pub const fn new(pages: &'static [Page], dwell_ms: u32) -> Self {
+        assert!(!pages.is_empty(), "a panel needs at least one page");
+        Self {
+            pages,
+            dwell_ms,
+            index: 0,
+            elapsed_ms: 0,
+        }
+    }
+
+    pub fn pages(&self) -> &'static [Page] {
+        self.pages
+    }
+
+    /// Page to draw now, `dt_ms` after the previous call.
+    pub fn update(&mut self, dt_ms: u32, alerts: Alerts) -> Page {
+        if let Some(alert) = alerts.iter().find(|a| self.pages.contains(&a.page())) {
+            // Resume the rotation from a fresh dwell once the alert clears.
+            self.elapsed_ms = 0;
+            return alert.page();
+        }
+
+        self.elapsed_ms = self.elapsed_ms.saturating_add(dt_ms);
+        if self.pages.len() > 1 && self.elapsed_ms >= self.dwell_ms {
+            self.elapsed_ms = 0;
+            self.index = (self.index + 1) % self.pages.len();
+        }
+        self.pages[self.index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sensors::{RailState, SenderState},
+        warnings::{Alert, WarningConfig, WarningEngine, WarningInputs},
+    };
+
+    const LEFT: &[Page] = &[Page::Coolant, Page::OilPressure];
+
+    #[test]
+    fn single_page_never_moves() {
+        let mut pager = Pager::new(&[Page::Fuel], 1_000);
+        for _ in 0..10 {
+            assert_eq!(pager.update(700, Alerts::default()), Page::Fuel);
+        }
+    }
+
+    #[test]
+    fn rotates_after_dwell() {
+        let mut pager = Pager::new(LEFT, 1_000);
+        assert_eq!(pager.update(0, Alerts::default()), Page::Coolant);
+        assert_eq!(pager.update(900, Alerts::default()), Page::Coolant);
+        assert_eq!(pager.update(100, Alerts::default()), Page::OilPressure);
+        assert_eq!(pager.update(1_000, Alerts::default()), Page::Coolant);
+    }
+
+    #[test]
+    fn alert_takes_over_its_panel() {
+        let mut engine = WarningEngine::new(WarningConfig::default());
+        let starving = WarningInputs {
+            coolant_f: 195.0,
+            battery_v: 13.8,
+            fuel_pct: 60.0,
+            oil_psi: 2.0,
+            engine_running: true,
+            coolant_sender: SenderState::Ok,
+            fuel_sender: SenderState::Ok,
+            oil_sender: SenderState::Ok,
+            reference: RailState::Ok,
+        };
+        let alerts = engine.update(&starving, 5_000);
+        assert_eq!(alerts.highest(), Some(Alert::LowOilPressure));
+
+        let mut left = Pager::new(LEFT, 1_000);
+        let mut right = Pager::new(&[Page::Fuel], 1_000);
+        for _ in 0..5 {
+            assert_eq!(left.update(1_000, alerts), Page::OilPressure);
+            assert_eq!(right.update(1_000, alerts), Page::Fuel);
+        }
+    }
+}
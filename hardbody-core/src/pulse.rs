@@ -0,0 +1,78 @@
+//! Period averaging for pulse-train inputs (tach, VSS).
+//!
+//! Timestamps come from a free-running, wrapping microsecond counter, so all
+//! differences use wrapping arithmetic.
+
+/// The last `N` periods between accepted edges.
+#[derive(Clone, Debug)]
+pub struct PulseTimer<const N: usize> {
+    /// Shorter periods than this are noise, not pulses (µs)
+    min_period_us: u32,
+    /// With no edge for this long the input has stopped (µs)
+    timeout_us: u32,
+    periods: [u32; N],
+    /// Periods recorded, saturating at `N`
+    len: usize,
+    next: usize,
+    last_edge_us: Option<u32>,
+}
+
+impl<const N: usize> PulseTimer<N> {
+    pub const fn new(min_period_us: u32, timeout_us: u32) -> Self {
+        assert!(N > 0, "need room for at least one period");
+        Self {
+            min_period_us,
+            timeout_us,
+            periods: [0; N],
+            len: 0,
+            next: 0,
+            last_edge_us: None,
+        }
+    }
+
+    /// Records an edge at `timestamp_us`. Returns `false` if it came too soon
+    /// after the last one and was dropped as noise.
+    pub fn on_edge(&mut self, timestamp_us: u32) -> bool {
+        let Some(last) = self.last_edge_us else {
+            self.last_edge_us = Some(timestamp_us);
+            return true;
+        };
+        let period = timestamp_us.wrapping_sub(last);
+        if period < self.min_period_us {
+            return false;
+        }
+        self.last_edge_us = Some(timestamp_us);
+        if period >= self.timeout_us {
+            // First edge after a stop; the gap isn't a running period.
+            self.clear();
+            return true;
+        }
+
+        self.periods[self.next] = period;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+        true
+    }
+
+    /// Mean recent period as of `now_us`, or `None` when stopped.
+    pub fn mean_period_us(&mut self, now_us: u32) -> Option<f32> {
+        let stopped = match self.last_edge_us {
+            Some(last) => now_us.wrapping_sub(last) >= self.timeout_us,
+            None => true,
+        };
+        if stopped {
+            self.clear();
+        }
+        if self.len == 0 {
+            return None;
+        }
+
+        let total: u64 = self.periods[..self.len].iter().map(|&p| p as u64).sum();
+        Some(total as f32 / self.len as f32)
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+}
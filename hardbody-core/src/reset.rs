@@ -0,0 +1,172 @@
+//! Why the cluster last reset, and how often it has.
+//!
+//! The RP2040's watchdog scratch registers survive every reset except
+//! power-on and the RUN pin, so the firmware keeps a small log in scratch
+//! 0..=3 across watchdog and software resets. Scratch 4..=7 belong to the
+//! boot ROM and are left alone.
+
+/// Marks scratch 0..=3 as holding a [`ResetLog`].
+const MAGIC: u32 = 0x4842_524C;
+
+/// What the firmware writes to scratch 3 just before resetting on purpose.
+pub const SOFTWARE_PENDING: u32 = 0x5357_5253;
+/// What the panic handler writes to scratch 3 before resetting.
+pub const PANIC_PENDING: u32 = 0x5041_4E43;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetReason {
+    /// Power applied or the brown-out detector tripped
+    PowerOn,
+    /// The RUN pin was pulled low
+    RunPin,
+    /// The main loop stopped feeding the watchdog
+    Watchdog,
+    /// The firmware reset itself, e.g. after giving up on recovery
+    Software,
+    /// The firmware panicked
+    Panic,
+    /// Anything else, such as a debugger
+    Unknown,
+}
+
+impl ResetReason {
+    pub fn name(self) -> &'static str {
+        match self {
+            ResetReason::PowerOn => "power-on",
+            ResetReason::RunPin => "reset pin",
+            ResetReason::Watchdog => "watchdog",
+            ResetReason::Software => "software",
+            ResetReason::Panic => "panic",
+            ResetReason::Unknown => "unknown",
+        }
+    }
+
+    /// For the boot screen.
+    pub fn label(self) -> &'static str {
+        match self {
+            ResetReason::PowerOn => "POWER ON",
+            ResetReason::RunPin => "RESET PIN",
+            ResetReason::Watchdog => "WATCHDOG",
+            ResetReason::Software => "SOFTWARE",
+            ResetReason::Panic => "PANIC",
+            ResetReason::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// The chip's own record of the last reset, read at boot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResetFlags {
+    /// `WATCHDOG.REASON.TIMER`
+    pub watchdog_timer: bool,
+    /// `CHIP_RESET.HAD_POR`; stays set through watchdog and software resets
+    pub power_on: bool,
+    /// `CHIP_RESET.HAD_RUN`
+    pub run_pin: bool,
+}
+
+/// Resets since the cluster was powered on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResetLog {
+    /// Why this boot happened
+    pub reason: ResetReason,
+    pub watchdog: u32,
+    /// Deliberate resets, panics included
+    pub software: u32,
+}
+
+impl ResetLog {
+    /// Works out why the chip reset and counts it.
+    ///
+    /// `scratch` is watchdog scratch 0..=3 as found at boot; it is updated
+    /// in place and should be written back.
+    pub fn on_boot(scratch: &mut [u32; 4], flags: ResetFlags) -> Self {
+        let valid = scratch[0] == MAGIC;
+        let (mut watchdog, mut software) = match valid {
+            true => (scratch[1], scratch[2]),
+            false => (0, 0),
+        };
+        // HAD_POR is sticky, so the flags are checked newest cause first
+        let pending = if valid { scratch[3] } else { 0 };
+        let reason = if pending == SOFTWARE_PENDING {
+            software = software.saturating_add(1);
+            ResetReason::Software
+        } else if pending == PANIC_PENDING {
+            software = software.saturating_add(1);
+            ResetReason::Panic
+        } else if flags.watchdog_timer {
+            watchdog = watchdog.saturating_add(1);
+            ResetReason::Watchdog
+        } else if flags.power_on {
+            ResetReason::PowerOn
+        } else if flags.run_pin {
+            ResetReason::RunPin
+        } else {
+            ResetReason::Unknown
+        };
+        *scratch = [MAGIC, watchdog, software, 0];
+        Self {
+            reason,
+            watchdog,
+            software,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POWER_ON: ResetFlags = ResetFlags {
+        watchdog_timer: false,
+        power_on: true,
+        run_pin: false,
+    };
+
+    #[test]
+    fn power_on_starts_a_fresh_log() {
+        // Power-on clears the scratch registers
+        let mut scratch = [0; 4];
+        let log = ResetLog::on_boot(&mut scratch, POWER_ON);
+        assert_eq!(log.reason, ResetReason::PowerOn);
+        assert_eq!((log.watchdog, log.software), (0, 0));
+        assert_eq!(scratch, [MAGIC, 0, 0, 0]);
+    }
+
+    #[test]
+    fn counts_accumulate_across_resets() {
+        let mut scratch = [0; 4];
+        ResetLog::on_boot(&mut scratch, POWER_ON);
+
+        let bite = ResetFlags {
+            watchdog_timer: true,
+            ..POWER_ON
+        };
+        let log = ResetLog::on_boot(&mut scratch, bite);
+        assert_eq!(log.reason, ResetReason::Watchdog);
+        assert_eq!(log.watchdog, 1);
+
+        scratch[3] = SOFTWARE_PENDING;
+        // The watchdog's REASON can outlive a software reset
+        let log = ResetLog::on_boot(&mut scratch, bite);
+        assert_eq!(log.reason, ResetReason::Software);
+        assert_eq!((log.watchdog, log.software), (1, 1));
+        assert_eq!(scratch[3], 0);
+
+        let log = ResetLog::on_boot(&mut scratch, bite);
+        assert_eq!((log.watchdog, log.software), (2, 1));
+
+        scratch[3] = PANIC_PENDING;
+        let log = ResetLog::on_boot(&mut scratch, POWER_ON);
+        assert_eq!(log.reason, ResetReason::Panic);
+        assert_eq!(log.software, 2);
+    }
+
+    #[test]
+    fn garbage_scratch_is_ignored() {
+        let mut scratch = [0xDEAD_BEEF, 7, 7, SOFTWARE_PENDING];
+        let log = ResetLog::on_boot(&mut scratch, ResetFlags::default());
+        assert_eq!(log.reason, ResetReason::Unknown);
+        assert_eq!((log.watchdog, log.software), (0, 0));
+    }
+}
@@ -0,0 +1,317 @@
+//! Filter stages between a raw ADC code and the conversions.
+//!
+//! Each stage takes codes one at a time and passes on a code when it has
+//! one; a pair of stages is itself a stage, so they chain as `(a, (b, c))`.
+//! [`Pipeline`] is the chain every input runs through, configured per
+//! input with [`FilterConfig`]:
+//!
+//! 1. [`Median`] of the last few conversions, so a single ignition spike
+//!    never makes it further
+//! 2. [`Oversample`], the mean of each block of conversions
+//! 3. [`Ema`], an optional exponential moving average
+//!
+//! The median goes first because an average would smear a spike over the
+//! following readings instead of dropping it. Everything is integer; the
+//! M0+ has no FPU.
+
+/// Longest median window.
+pub const MEDIAN_MAX: usize = 7;
+
+/// One step of a filter chain.
+pub trait Stage {
+    /// Feeds in a code, returning one to pass on if there is one yet.
+    fn push(&mut self, code: i16) -> Option<i16>;
+    /// Forgets everything seen so far.
+    fn reset(&mut self);
+}
+
+impl<A: Stage, B: Stage> Stage for (A, B) {
+    fn push(&mut self, code: i16) -> Option<i16> {
+        self.0.push(code).and_then(|code| self.1.push(code))
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+        self.1.reset();
+    }
+}
+
+/// Median of the last `k` codes, passed on for every code in.
+///
+/// Rejects any spike shorter than half the window. Until the window fills,
+/// the median is of what has arrived so far.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Median {
+    window: [i16; MEDIAN_MAX],
+    k: u8,
+    len: u8,
+    next: u8,
+}
+
+impl Median {
+    /// `k` is clamped to `1..=MEDIAN_MAX`; 1 passes codes straight through.
+    pub const fn new(k: u8) -> Self {
+        let k = if k == 0 {
+            1
+        } else if k as usize > MEDIAN_MAX {
+            MEDIAN_MAX as u8
+        } else {
+            k
+        };
+        Self {
+            window: [0; MEDIAN_MAX],
+            k,
+            len: 0,
+            next: 0,
+        }
+    }
+}
+
+impl Stage for Median {
+    fn push(&mut self, code: i16) -> Option<i16> {
+        self.window[self.next as usize] = code;
+        self.next = (self.next + 1) % self.k;
+        self.len = (self.len + 1).min(self.k);
+
+        let mut sorted = self.window;
+        let sorted = &mut sorted[..self.len as usize];
+        sorted.sort_unstable();
+        Some(sorted[(sorted.len() - 1) / 2])
+    }
+
+    fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+}
+
+/// Mean of each block of `n` codes, passed on once per block.
+///
+/// Averaging `n` conversions cuts random noise by √n, at `n` times the
+/// conversions per reading.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Oversample {
+    n: u16,
+    sum: i32,
+    count: u16,
+}
+
+impl Oversample {
+    /// `n` of 0 is taken as 1, which passes codes straight through.
+    pub const fn new(n: u16) -> Self {
+        Self {
+            n: if n == 0 { 1 } else { n },
+            sum: 0,
+            count: 0,
+        }
+    }
+}
+
+impl Stage for Oversample {
+    fn push(&mut self, code: i16) -> Option<i16> {
+        self.sum += code as i32;
+        self.count += 1;
+        if self.count < self.n {
+            return None;
+        }
+        let n = self.n as i32;
+        let mean = (self.sum + n / 2).div_euclid(n);
+        self.reset();
+        Some(mean as i16)
+    }
+
+    fn reset(&mut self) {
+        self.sum = 0;
+        self.count = 0;
+    }
+}
+
+/// Exponential moving average with a weight of `1 / 2^shift` on each new
+/// code; a shift of 0 passes codes straight through.
+///
+/// Reaches 63 % of a step in about `2^shift` codes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ema {
+    shift: u8,
+    /// Q8, so small steps aren't lost to rounding
+    state: Option<i32>,
+}
+
+impl Ema {
+    pub const fn new(shift: u8) -> Self {
+        Self { shift, state: None }
+    }
+}
+
+impl Stage for Ema {
+    fn push(&mut self, code: i16) -> Option<i16> {
+        let target = (code as i32) << 8;
+        let state = match self.state {
+            // Start from the first code rather than ramp up from zero
+            None => target,
+            Some(state) => state + ((target - state) >> self.shift),
+        };
+        self.state = Some(state);
+        Some(((state + 128) >> 8) as i16)
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/// How one input's conversions are filtered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FilterConfig {
+    /// Median window; 1 for none
+    pub median: u8,
+    /// Conversions averaged per reading; 1 for none
+    pub oversample: u16,
+    /// EMA weight as a shift; 0 for none
+    pub ema_shift: u8,
+}
+
+impl FilterConfig {
+    /// Every conversion passed on as it came.
+    pub const RAW: Self = Self {
+        median: 1,
+        oversample: 1,
+        ema_shift: 0,
+    };
+}
+
+/// Median, then oversampling, then the EMA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pipeline {
+    stages: (Median, (Oversample, Ema)),
+}
+
+impl Pipeline {
+    pub const fn new(config: FilterConfig) -> Self {
+        Self {
+            stages: (
+                Median::new(config.median),
+                (
+                    Oversample::new(config.oversample),
+                    Ema::new(config.ema_shift),
+                ),
+            ),
+        }
+    }
+}
+
+impl Stage for Pipeline {
+    fn push(&mut self, code: i16) -> Option<i16> {
+        self.stages.push(code)
+    }
+
+    fn reset(&mut self) {
+        self.stages.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// Repeatable noise in `-amplitude..=amplitude`.
+    struct Noise(u32);
+
+    impl Noise {
+        fn next(&mut self, amplitude: i16) -> i16 {
+            self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let unit = (self.0 >> 16) as i32 % (2 * amplitude as i32 + 1);
+            (unit - amplitude as i32) as i16
+        }
+    }
+
+    /// A steady 12000 with ±40 codes of noise and a full-scale spike every
+    /// 23rd conversion.
+    fn noisy_sender(len: usize) -> Vec<i16> {
+        let mut noise = Noise(1);
+        (0..len)
+            .map(|i| match i % 23 {
+                11 => i16::MAX,
+                _ => 12_000 + noise.next(40),
+            })
+            .collect()
+    }
+
+    fn run(stage: &mut impl Stage, codes: &[i16]) -> Vec<i16> {
+        codes.iter().filter_map(|&code| stage.push(code)).collect()
+    }
+
+    fn worst_error(out: &[i16], truth: i16) -> i16 {
+        out.iter().map(|&c| (c - truth).abs()).max().unwrap()
+    }
+
+    #[test]
+    fn raw_passes_everything_through() {
+        let codes = noisy_sender(100);
+        assert_eq!(run(&mut Pipeline::new(FilterConfig::RAW), &codes), codes);
+    }
+
+    #[test]
+    fn median_drops_single_spikes() {
+        let codes = noisy_sender(500);
+        assert!(worst_error(&codes, 12_000) > 20_000);
+        let out = run(&mut Median::new(3), &codes);
+        assert_eq!(out.len(), codes.len());
+        assert!(worst_error(&out, 12_000) <= 40);
+    }
+
+    #[test]
+    fn median_fills_up_first() {
+        let mut median = Median::new(5);
+        assert_eq!(median.push(10), Some(10));
+        assert_eq!(median.push(30), Some(10));
+        assert_eq!(median.push(20), Some(20));
+        assert_eq!(Median::new(0), Median::new(1));
+        assert_eq!(Median::new(200).k as usize, MEDIAN_MAX);
+    }
+
+    #[test]
+    fn oversampling_averages_blocks() {
+        let mut over = Oversample::new(4);
+        assert_eq!(run(&mut over, &[1, 2, 3, 4, 10, 10, 10, 11]), [3, 10]);
+        assert_eq!(run(&mut over, &[-3, -3, -3, -4]), [-3]);
+    }
+
+    #[test]
+    fn oversampling_cuts_noise() {
+        let mut noise = Noise(7);
+        let codes: Vec<i16> = (0..4_000).map(|_| 500 + noise.next(100)).collect();
+        let out = run(&mut Oversample::new(16), &codes);
+        assert_eq!(out.len(), 250);
+        assert!(worst_error(&out, 500) <= 50, "{}", worst_error(&out, 500));
+    }
+
+    #[test]
+    fn ema_follows_a_step() {
+        let mut ema = Ema::new(3);
+        assert_eq!(ema.push(1_000), Some(1_000));
+        let out = run(&mut ema, &[2_000; 8]);
+        // 1 - (7/8)^8 of the way after eight codes
+        assert!((out[7] - 1_656).abs() <= 1, "{:?}", out);
+        let out = run(&mut ema, &[2_000; 100]);
+        assert_eq!(*out.last().unwrap(), 2_000);
+        assert_eq!(run(&mut Ema::new(0), &[5, -5]), [5, -5]);
+    }
+
+    #[test]
+    fn pipeline_cleans_a_noisy_sender() {
+        let codes = noisy_sender(2_000);
+        let mut pipeline = Pipeline::new(FilterConfig {
+            median: 3,
+            oversample: 4,
+            ema_shift: 2,
+        });
+        let out = run(&mut pipeline, &codes);
+        assert_eq!(out.len(), 500);
+        assert!(worst_error(&out[10..], 12_000) <= 25, "{:?}", out);
+
+        pipeline.reset();
+        assert_eq!(run(&mut pipeline, &[100; 4]), [100]);
+    }
+}
@@ -0,0 +1,216 @@
+//! Resistive senders read through a pull-up to the 3.3V rail.
+//!
+//! Every sender on the cluster is wired the same way: sender to ground,
+//! pull-up to the rail, the junction on one ADC input and the rail itself
+//! on A3 of the same ADC. Only the pull-up, the span a working sender can
+//! produce and what its resistance means change from one to the next, so a
+//! new channel is a [`ResistiveSender`] with those filled in rather than
+//! another copy of the divider maths.
+
+use crate::{
+    curve::Curve,
+    sensors::{MinMax, SenderLimits, SenderState, ADC_MAX, RAIL_WINDOW},
+    thermistor::Thermistor,
+};
+
+/// Highest ratio either conversion path reports, so a sender reading the
+/// full rail still gives a finite resistance.
+pub(crate) const RATIO_MAX: f32 = 0.999_999;
+
+/// Sender resistance → whatever the sender measures, in its own unit.
+pub trait Transfer {
+    fn eval(&self, ohms: f32) -> f32;
+}
+
+impl<T: Transfer + ?Sized> Transfer for &T {
+    fn eval(&self, ohms: f32) -> f32 {
+        (**self).eval(ohms)
+    }
+}
+
+/// A measured calibration table.
+impl<const N: usize> Transfer for Curve<N> {
+    fn eval(&self, ohms: f32) -> f32 {
+        Curve::eval(self, ohms)
+    }
+}
+
+/// °F, from a Beta or Steinhart–Hart model.
+impl Transfer for Thermistor {
+    fn eval(&self, ohms: f32) -> f32 {
+        // ln(0) is -inf; a dead short reads as very hot instead
+        self.fahrenheit(ohms.max(0.001))
+    }
+}
+
+/// A sender sold as a straight line, e.g. 0–80 psi over 10–180 Ω.
+///
+/// Not clamped: past either end it keeps going, and [`SenderLimits`] is
+/// what catches a sender that far off.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Linear {
+    /// Value at 0 Ω
+    pub offset: f32,
+    pub per_ohm: f32,
+}
+
+impl Linear {
+    /// The line through two `(ohms, value)` points.
+    pub const fn through(a: (f32, f32), b: (f32, f32)) -> Self {
+        let per_ohm = (b.1 - a.1) / (b.0 - a.0);
+        Self {
+            offset: a.1 - per_ohm * a.0,
+            per_ohm,
+        }
+    }
+}
+
+impl Transfer for Linear {
+    fn eval(&self, ohms: f32) -> f32 {
+        self.offset + self.per_ohm * ohms
+    }
+}
+
+/// One sender channel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResistiveSender<T> {
+    pub pull_up_ohms: f32,
+    /// Fraction either way the pull-up may really be, e.g. 0.01 for 1 %
+    pub pull_up_tolerance: f32,
+    pub limits: SenderLimits,
+    pub transfer: T,
+}
+
+/// What a [`ResistiveSender`] makes of one pair of codes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SenderReading {
+    /// V_sense / V_3v3, clamped to `0.0..1.0`
+    pub ratio: f32,
+    /// Sender resistance in ohms, taking the pull-up at its nominal value
+    pub ohms: f32,
+    pub value: f32,
+    /// Where `value` could be with the pull-up anywhere in its tolerance
+    pub bounds: MinMax,
+    pub state: SenderState,
+}
+
+impl<T: Transfer> ResistiveSender<T> {
+    pub const fn new(
+        pull_up_ohms: f32,
+        pull_up_tolerance: f32,
+        limits: SenderLimits,
+        transfer: T,
+    ) -> Self {
+        Self {
+            pull_up_ohms,
+            pull_up_tolerance,
+            limits,
+            transfer,
+        }
+    }
+
+    /// - `adc`     = the sender's raw code
+    /// - `v33_adc` = 3.3V rail raw code, from the same ADC
+    pub fn read(&self, adc: i16, v33_adc: i16) -> SenderReading {
+        // ratio = V_sense / V_3v3 = code_sense / code_v33
+        let v33 = (v33_adc.max(1) as f32).min(ADC_MAX); // avoid /0, clamp top
+        let ratio = ((adc.max(0) as f32).min(ADC_MAX) / v33).clamp(0.0, RATIO_MAX);
+        let ohms = self.ohms(ratio);
+
+        // The sender's resistance scales with the pull-up's, so its
+        // tolerance carries straight through to the ohms
+        let mut bounds = MinMax::new(self.transfer.eval(ohms * (1.0 - self.pull_up_tolerance)));
+        bounds.update(self.transfer.eval(ohms * (1.0 + self.pull_up_tolerance)));
+
+        SenderReading {
+            ratio,
+            ohms,
+            value: self.transfer.eval(ohms),
+            bounds,
+            state: self.limits.classify(ratio, RAIL_WINDOW.classify(v33_adc)),
+        }
+    }
+
+    /// R_sender = R_pull · ratio / (1 − ratio)
+    pub fn ohms(&self, ratio: f32) -> f32 {
+        self.pull_up_ohms * ratio / (1.0 - ratio)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sensors::{DEFAULT_FUEL_CURVE, FUEL_LIMITS},
+        thermistor::{SteinhartHart, DEFAULT_THERMISTOR},
+    };
+
+    const V33: i16 = 26_400;
+
+    fn code(pull_up_ohms: f32, ohms: f32) -> i16 {
+        (V33 as f32 * ohms / (pull_up_ohms + ohms)) as i16
+    }
+
+    #[test]
+    fn linear_through_its_points() {
+        let oil = Linear::through((10.0, 0.0), (180.0, 80.0));
+        assert!(oil.eval(10.0).abs() < 1e-4);
+        assert!((oil.eval(95.0) - 40.0).abs() < 1e-4);
+        assert!((oil.eval(180.0) - 80.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn every_transfer_reads_through_the_divider() {
+        let limits = SenderLimits {
+            short_below: 0.001,
+            open_above: 0.99,
+        };
+        let table = ResistiveSender::new(1_000.0, 0.01, FUEL_LIMITS, DEFAULT_FUEL_CURVE);
+        let reading = table.read(code(1_000.0, 48.0), V33);
+        assert!((reading.ohms - 48.0).abs() < 0.1, "{:?}", reading);
+        assert!((reading.value - 48.3).abs() < 0.2, "{:?}", reading);
+
+        let beta = ResistiveSender::new(1_000.0, 0.01, limits, DEFAULT_THERMISTOR);
+        assert!((beta.read(code(1_000.0, 325.0), V33).value - 77.0).abs() < 0.5);
+
+        // A transmission temperature sender on its own 2.2k pull-up is just
+        // another channel
+        let points = [(3_520.0, 32.0), (185.0, 160.0), (47.0, 240.0)];
+        let trans = Thermistor::SteinhartHart(SteinhartHart::from_points(points).unwrap());
+        let trans = ResistiveSender::new(2_200.0, 0.01, limits, trans);
+        assert!((trans.read(code(2_200.0, 185.0), V33).value - 160.0).abs() < 0.5);
+
+        let linear = Linear::through((10.0, 0.0), (180.0, 80.0));
+        let linear = ResistiveSender::new(1_000.0, 0.01, limits, &linear);
+        assert!((linear.read(code(1_000.0, 95.0), V33).value - 40.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn bounds_follow_the_pull_up_tolerance() {
+        let mut sender = ResistiveSender::new(1_000.0, 0.01, FUEL_LIMITS, DEFAULT_FUEL_CURVE);
+        let exact = sender.read(code(1_000.0, 48.0), V33);
+        assert!(exact.bounds.min < exact.value && exact.value < exact.bounds.max);
+
+        // Around 48 Ω the tank moves about 1.1 % per ohm, so ±5 % of the
+        // pull-up is about ±2.7 %
+        sender.pull_up_tolerance = 0.05;
+        let loose = sender.read(code(1_000.0, 48.0), V33);
+        let spread = loose.bounds.max - loose.bounds.min;
+        assert!((5.2..5.6).contains(&spread), "{:?}", loose);
+
+        sender.pull_up_tolerance = 0.0;
+        let ideal = sender.read(code(1_000.0, 48.0), V33);
+        assert_eq!(ideal.bounds, MinMax::new(ideal.value));
+    }
+
+    #[test]
+    fn thermistor_short_is_hot_not_nan() {
+        let limits = SenderLimits {
+            short_below: 0.003,
+            open_above: 0.97,
+        };
+        let reading = ResistiveSender::new(1_000.0, 0.01, limits, DEFAULT_THERMISTOR).read(0, V33);
+        assert!(reading.value > 400.0, "{:?}", reading);
+        assert_eq!(reading.state, SenderState::Short);
+    }
+}
@@ -0,0 +1,576 @@
+//! Pure conversions from raw ADS1115 codes to physical readings.
+//!
+//! Nothing in here touches a display or a bus, so the same numbers can feed
+//! the gauges, alarms, logging or a serial console.
+
+use crate::{
+    curve::Curve,
+    odometer::Odometer,
+    sender::{ResistiveSender, Transfer},
+    speed::SpeedReading,
+    tach::TachReading,
+    thermistor::Thermistor,
+};
+
+// ADS1115 transfer
+pub(crate) const FS_V: f32 = 4.096; // ±4.096 V PGA
+pub(crate) const ADC_MAX: f32 = 32767.0;
+
+/// Resistor values on the board around the ADC inputs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Circuit {
+    /// Resistive senders are pulled up to the 3.3V rail through this
+    pub pull_up_ohms: f32,
+    /// Fraction either way the pull-ups may really be, e.g. 0.01 for 1 %
+    pub pull_up_tolerance: f32,
+    /// Battery divider, top leg
+    pub battery_r1_ohms: f32,
+    /// Battery divider, bottom leg
+    pub battery_r2_ohms: f32,
+}
+
+/// Values as fitted: 1 kΩ 1 % pull-ups, 100k/22k battery divider.
+pub const DEFAULT_CIRCUIT: Circuit = Circuit {
+    pull_up_ohms: 1_000.0,
+    pull_up_tolerance: 0.01,
+    battery_r1_ohms: 100_000.0,
+    battery_r2_ohms: 22_000.0,
+};
+
+impl Circuit {
+    /// A sender on one of the board's pull-ups.
+    pub fn sender<T: Transfer>(&self, limits: SenderLimits, transfer: T) -> ResistiveSender<T> {
+        ResistiveSender::new(self.pull_up_ohms, self.pull_up_tolerance, limits, transfer)
+    }
+}
+
+/// Volts at the ADC pin for a raw code, clamped to the positive range.
+pub fn code_to_volts(code: i16) -> f32 {
+    (code.max(0) as f32).min(ADC_MAX) * FS_V / ADC_MAX
+}
+
+/// Whether a resistive sender's wiring looks sound from the ADC.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SenderState {
+    #[default]
+    Ok,
+    /// Broken wire or missing sender; the pin sits near the rail
+    Open,
+    /// Shorted to ground
+    Short,
+    /// The 3.3V reference is out of its window, so the ratio means nothing
+    NoReference,
+}
+
+impl SenderState {
+    pub fn is_ok(self) -> bool {
+        self == SenderState::Ok
+    }
+
+    /// Open or shorted; a bad reference says nothing about the wiring.
+    pub fn is_broken(self) -> bool {
+        matches!(self, SenderState::Open | SenderState::Short)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SenderState::Ok => "ok",
+            SenderState::Open => "open",
+            SenderState::Short => "short",
+            SenderState::NoReference => "no ref",
+        }
+    }
+
+    /// Shown in place of the value; four characters, like a short reading.
+    pub fn label(self) -> &'static str {
+        match self {
+            SenderState::Ok => "",
+            SenderState::Open => "OPEN",
+            SenderState::Short => "SHRT",
+            SenderState::NoReference => "REF",
+        }
+    }
+}
+
+/// The span of `V_sense / V_3v3` a working sender can produce. Anything
+/// outside it is wiring, not a reading.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SenderLimits {
+    /// Below this the sender is taken as shorted to ground
+    pub short_below: f32,
+    /// Above this it's taken as open
+    pub open_above: f32,
+}
+
+impl SenderLimits {
+    /// `rail` is the reference the ratio was taken against; a ratio to a
+    /// bad rail can't tell a short from a cold engine.
+    pub fn classify(&self, ratio: f32, rail: RailState) -> SenderState {
+        if !rail.is_ok() {
+            SenderState::NoReference
+        } else if ratio < self.short_below {
+            SenderState::Short
+        } else if ratio > self.open_above {
+            SenderState::Open
+        } else {
+            SenderState::Ok
+        }
+    }
+}
+
+/// Through the 1 kΩ pull-up: under 2 Ω is a short, the sender itself never
+/// reads below 3.8 Ω; over 1 kΩ is open, against 93 Ω empty.
+pub const FUEL_LIMITS: SenderLimits = SenderLimits {
+    short_below: 0.002,
+    open_above: 0.5,
+};
+
+/// Under 3 Ω is a short, around 370 °F on the stock thermistor; over
+/// 32 kΩ is open, well past its -40 °F.
+pub const COOLANT_LIMITS: SenderLimits = SenderLimits {
+    short_below: 0.003,
+    open_above: 0.97,
+};
+
+/// Under 4 Ω is a short, against 10 Ω at rest; over 1 kΩ is open.
+pub const OIL_LIMITS: SenderLimits = SenderLimits {
+    short_below: 0.004,
+    open_above: 0.5,
+};
+
+pub const FUEL_CURVE_POINTS: usize = 9;
+
+/// Sender resistance (Ω) → tank level (%).
+pub type FuelCurve = Curve<FUEL_CURVE_POINTS>;
+
+/// OEM sender span, 3.8 Ω full to 93 Ω empty, spaced to match the old
+/// straight-line mapping. Replace with a measured curve per truck.
+pub const DEFAULT_FUEL_CURVE: FuelCurve = Curve::new([
+    (3.8, 100.0),
+    (15.0, 86.5),
+    (26.0, 73.5),
+    (37.0, 60.8),
+    (48.0, 48.3),
+    (59.0, 36.1),
+    (70.0, 24.2),
+    (81.0, 12.5),
+    (93.0, 0.0),
+]);
+
+/// Fuel level from the ratiometric sender on A1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FuelReading {
+    /// V_sense / V_3v3, clamped to `0.0..1.0`
+    pub ratio: f32,
+    /// Sender resistance in ohms
+    pub ohms: f32,
+    /// Tank level, 0..=100
+    pub percent: u8,
+    /// Where `percent` could be across the pull-up's tolerance
+    pub bounds: MinMax,
+    pub state: SenderState,
+}
+
+impl FuelReading {
+    /// - `adc`     = fuel sender (A1) raw i16
+    /// - `v33_adc` = 3.3V rail (A3) raw i16
+    /// - `curve`   = tank calibration
+    pub fn from_codes(adc: i16, v33_adc: i16, curve: &FuelCurve, circuit: &Circuit) -> Self {
+        let reading = circuit.sender(FUEL_LIMITS, curve).read(adc, v33_adc);
+        let MinMax { min, max } = reading.bounds;
+        Self {
+            ratio: reading.ratio,
+            ohms: reading.ohms,
+            percent: (reading.value.clamp(0.0, 100.0) + 0.5) as u8,
+            bounds: MinMax {
+                min: min.clamp(0.0, 100.0),
+                max: max.clamp(0.0, 100.0),
+            },
+            state: reading.state,
+        }
+    }
+}
+
+pub const OIL_CURVE_POINTS: usize = 5;
+
+/// Sender resistance (Ω) → oil pressure (psi).
+pub type OilPressureCurve = Curve<OIL_CURVE_POINTS>;
+
+/// VDO-style 0–80 psi sender, 10 Ω at rest rising to ~180 Ω.
+pub const DEFAULT_OIL_CURVE: OilPressureCurve = Curve::new([
+    (10.0, 0.0),
+    (52.0, 20.0),
+    (88.0, 40.0),
+    (124.0, 60.0),
+    (180.0, 80.0),
+]);
+
+/// Oil pressure from the ratiometric sender on the second ADC's A0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OilPressureReading {
+    /// V_sense / V_3v3, clamped to `0.0..1.0`
+    pub ratio: f32,
+    /// Sender resistance in ohms
+    pub ohms: f32,
+    pub psi: f32,
+    /// Where `psi` could be across the pull-up's tolerance
+    pub bounds: MinMax,
+    pub state: SenderState,
+}
+
+impl OilPressureReading {
+    /// - `adc`     = oil pressure sender (ADC2 A0) raw i16
+    /// - `v33_adc` = 3.3V rail (ADC2 A3) raw i16
+    /// - `curve`   = sender calibration
+    pub fn from_codes(adc: i16, v33_adc: i16, curve: &OilPressureCurve, circuit: &Circuit) -> Self {
+        let reading = circuit.sender(OIL_LIMITS, curve).read(adc, v33_adc);
+        Self {
+            ratio: reading.ratio,
+            ohms: reading.ohms,
+            psi: reading.value,
+            bounds: reading.bounds,
+            state: reading.state,
+        }
+    }
+}
+
+/// Coolant temperature from the thermistor on A0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CoolantReading {
+    /// Thermistor resistance in ohms
+    pub ohms: f32,
+    pub fahrenheit: f32,
+    /// Where `fahrenheit` could be across the pull-up's tolerance
+    pub bounds: MinMax,
+    pub state: SenderState,
+}
+
+impl CoolantReading {
+    /// - `adc`        = thermistor (A0) raw i16 (0..32767 expected)
+    /// - `v33_adc`    = 3.3V rail (A3) raw i16
+    /// - `thermistor` = sender model
+    pub fn from_codes(adc: i16, v33_adc: i16, thermistor: &Thermistor, circuit: &Circuit) -> Self {
+        let reading = circuit
+            .sender(COOLANT_LIMITS, thermistor)
+            .read(adc, v33_adc);
+        Self {
+            ohms: reading.ohms,
+            fahrenheit: reading.value,
+            bounds: reading.bounds,
+            state: reading.state,
+        }
+    }
+}
+
+/// Battery voltage through the R1/R2 divider on A2.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BatteryReading {
+    pub volts: f32,
+}
+
+impl BatteryReading {
+    pub fn from_code(batt_adc: i16, circuit: &Circuit) -> Self {
+        let v_batt_sense = code_to_volts(batt_adc); // volts at A2
+        let (r1, r2) = (circuit.battery_r1_ohms, circuit.battery_r2_ohms);
+        Self {
+            volts: v_batt_sense * (r1 + r2) / r2, // divider scaled
+        }
+    }
+}
+
+/// Whether the 3.3V rail is where the ratiometric senders need it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RailState {
+    #[default]
+    Ok,
+    Low,
+    High,
+}
+
+impl RailState {
+    pub fn is_ok(self) -> bool {
+        self == RailState::Ok
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RailState::Ok => "ok",
+            RailState::Low => "low",
+            RailState::High => "high",
+        }
+    }
+
+    /// `self` unless it's fine, then `other`; for a rail seen by two ADCs.
+    pub fn or(self, other: RailState) -> RailState {
+        if self.is_ok() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+/// Where the 3.3V rail has to sit for a ratio against it to be trusted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RailWindow {
+    pub low_v: f32,
+    pub high_v: f32,
+}
+
+impl RailWindow {
+    /// Classifies the rail from its raw A3 code.
+    pub fn classify(&self, v33_adc: i16) -> RailState {
+        let volts = code_to_volts(v33_adc);
+        if volts < self.low_v {
+            RailState::Low
+        } else if volts > self.high_v {
+            RailState::High
+        } else {
+            RailState::Ok
+        }
+    }
+}
+
+/// ±5 %. The regulator holds a few percent; outside that the rail is
+/// browning out or A3 has come loose, and the senders with it.
+pub const RAIL_WINDOW: RailWindow = RailWindow {
+    low_v: 3.135,
+    high_v: 3.465,
+};
+
+/// The 3.3V rail as measured on A3, used as the ratiometric reference.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReferenceReading {
+    pub volts: f32,
+    pub state: RailState,
+}
+
+impl ReferenceReading {
+    pub fn from_code(v33_adc: i16) -> Self {
+        Self {
+            volts: code_to_volts(v33_adc.max(1)),
+            state: RAIL_WINDOW.classify(v33_adc),
+        }
+    }
+}
+
+/// Lowest and highest of a reading so far.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinMax {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl MinMax {
+    pub const fn new(value: f32) -> Self {
+        Self {
+            min: value,
+            max: value,
+        }
+    }
+
+    pub fn update(&mut self, value: f32) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+}
+
+/// Every reading the screens and warnings use, taken once per loop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Snapshot {
+    pub coolant: CoolantReading,
+    /// Coolant range since power-on, from readings that could be trusted
+    pub coolant_range: Option<MinMax>,
+    pub reference: ReferenceReading,
+    pub fuel: FuelReading,
+    pub battery: BatteryReading,
+    pub oil: OilPressureReading,
+    pub tach: TachReading,
+    pub speed: SpeedReading,
+    pub odometer: Odometer,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::thermistor::DEFAULT_THERMISTOR;
+
+    /// Code a sender of `ohms` produces against a pull-up, as a fraction of `v33`.
+    fn sender_code(ohms: f32, v33: i16) -> i16 {
+        (v33 as f32 * ohms / (DEFAULT_CIRCUIT.pull_up_ohms + ohms)) as i16
+    }
+
+    fn fuel(adc: i16, v33_adc: i16) -> FuelReading {
+        FuelReading::from_codes(adc, v33_adc, &DEFAULT_FUEL_CURVE, &DEFAULT_CIRCUIT)
+    }
+
+    #[test]
+    fn fuel_endpoints() {
+        let v33 = 26_400;
+        assert_eq!(fuel(sender_code(3.8, v33), v33).percent, 100);
+        assert_eq!(fuel(sender_code(93.0, v33), v33).percent, 0);
+    }
+
+    #[test]
+    fn fuel_is_ratiometric() {
+        let a = fuel(sender_code(48.0, 26_400), 26_400);
+        let b = fuel(sender_code(48.0, 25_500), 25_500);
+        assert_eq!(a.state, SenderState::Ok);
+        assert_eq!(b.state, SenderState::Ok);
+        assert!((a.percent as i16 - b.percent as i16).abs() <= 1);
+    }
+
+    #[test]
+    fn fuel_clamps_out_of_range() {
+        assert_eq!(fuel(-5, 26_400).percent, 100);
+        assert_eq!(fuel(26_400, 26_400).percent, 0);
+        assert_eq!(fuel(100, 0).percent, 0);
+    }
+
+    #[test]
+    fn fuel_follows_calibration_curve() {
+        // A tank that reads half-full well before the sender's midpoint
+        let curve = FuelCurve::new([
+            (3.8, 100.0),
+            (10.0, 90.0),
+            (20.0, 75.0),
+            (30.0, 50.0),
+            (40.0, 35.0),
+            (55.0, 20.0),
+            (70.0, 10.0),
+            (85.0, 5.0),
+            (93.0, 0.0),
+        ]);
+        let v33 = 26_400;
+        let reading =
+            FuelReading::from_codes(sender_code(30.0, v33), v33, &curve, &DEFAULT_CIRCUIT);
+        assert!((reading.ohms - 30.0).abs() < 0.1, "{:?}", reading);
+        assert!((reading.percent as i16 - 50).abs() <= 1, "{:?}", reading);
+    }
+
+    fn coolant(adc: i16, v33_adc: i16) -> CoolantReading {
+        CoolantReading::from_codes(adc, v33_adc, &DEFAULT_THERMISTOR, &DEFAULT_CIRCUIT)
+    }
+
+    #[test]
+    fn coolant_at_r25_is_77f() {
+        let v33 = 26_400;
+        let reading = coolant(sender_code(325.0, v33), v33);
+        assert!((reading.ohms - 325.0).abs() < 1.0, "{:?}", reading);
+        assert!((reading.fahrenheit - 77.0).abs() < 1.0, "{:?}", reading);
+    }
+
+    #[test]
+    fn coolant_rises_as_resistance_falls() {
+        let v33 = 26_400;
+        let warm = coolant(sender_code(100.0, v33), v33);
+        let hot = coolant(sender_code(30.0, v33), v33);
+        assert!(hot.fahrenheit > warm.fahrenheit);
+    }
+
+    #[test]
+    fn oil_pressure_follows_curve() {
+        let v33 = 26_400;
+        let oil = |ohms| {
+            let code = sender_code(ohms, v33);
+            OilPressureReading::from_codes(code, v33, &DEFAULT_OIL_CURVE, &DEFAULT_CIRCUIT)
+        };
+        assert!(oil(10.0).psi < 0.5);
+        assert!((oil(70.0).psi - 30.0).abs() < 0.5, "{:?}", oil(70.0));
+        assert!((oil(180.0).psi - 80.0).abs() < 0.5);
+        // Broken wire reads as pegged, not beyond the gauge
+        assert_eq!(oil(1e6).psi, 80.0);
+    }
+
+    #[test]
+    fn battery_divider() {
+        // 12.6 V behind 100k/22k is ~2.272 V at the pin
+        let code = (2.272 / FS_V * ADC_MAX) as i16;
+        let reading = BatteryReading::from_code(code, &DEFAULT_CIRCUIT);
+        assert!((reading.volts - 12.6).abs() < 0.01, "{:?}", reading);
+    }
+
+    #[test]
+    fn pull_up_is_configurable() {
+        // Same pin voltage, but a 2.2k pull-up means a bigger sender
+        let circuit = Circuit {
+            pull_up_ohms: 2_200.0,
+            ..DEFAULT_CIRCUIT
+        };
+        let v33 = 26_400;
+        let code = sender_code(100.0, v33);
+        let reading = CoolantReading::from_codes(code, v33, &DEFAULT_THERMISTOR, &circuit);
+        assert!((reading.ohms - 220.0).abs() < 1.0, "{:?}", reading);
+    }
+
+    #[test]
+    fn broken_wiring_is_classified() {
+        let v33 = 26_400;
+        assert_eq!(fuel(sender_code(48.0, v33), v33).state, SenderState::Ok);
+        assert_eq!(fuel(v33, v33).state, SenderState::Open);
+        assert_eq!(fuel(0, v33).state, SenderState::Short);
+
+        // The whole gauge range is a valid reading
+        for ohms in [13_000.0, 325.0, 11.0] {
+            assert!(
+                coolant(sender_code(ohms, v33), v33).state.is_ok(),
+                "{}",
+                ohms
+            );
+        }
+        assert_eq!(coolant(v33 - 10, v33).state, SenderState::Open);
+        assert_eq!(coolant(20, v33).state, SenderState::Short);
+
+        let oil = |code| {
+            OilPressureReading::from_codes(code, v33, &DEFAULT_OIL_CURVE, &DEFAULT_CIRCUIT).state
+        };
+        assert_eq!(oil(sender_code(10.0, v33)), SenderState::Ok);
+        assert_eq!(oil(sender_code(2.0, v33)), SenderState::Short);
+        assert_eq!(oil(sender_code(5_000.0, v33)), SenderState::Open);
+    }
+
+    #[test]
+    fn reference_volts() {
+        assert!((ReferenceReading::from_code(26_400).volts - 3.30).abs() < 0.01);
+        assert!(ReferenceReading::from_code(-1).volts > 0.0);
+    }
+
+    #[test]
+    fn reference_window() {
+        let state = |code| ReferenceReading::from_code(code).state;
+        assert_eq!(state(26_400), RailState::Ok);
+        assert_eq!(state(25_200), RailState::Ok);
+        assert_eq!(state(24_000), RailState::Low);
+        assert_eq!(state(-1), RailState::Low);
+        assert_eq!(state(28_000), RailState::High);
+        assert_eq!(RailState::Ok.or(RailState::High), RailState::High);
+        assert_eq!(RailState::Low.or(RailState::High), RailState::Low);
+    }
+
+    #[test]
+    fn bad_reference_overrides_the_senders() {
+        // A sagging rail, and a fuel reading that would look fine against it
+        let v33 = 24_000;
+        let reading = fuel(sender_code(48.0, v33), v33);
+        assert_eq!(reading.state, SenderState::NoReference);
+        assert!(!reading.state.is_broken());
+        assert_eq!(coolant(0, v33).state, SenderState::NoReference);
+        let oil = OilPressureReading::from_codes(0, 30_000, &DEFAULT_OIL_CURVE, &DEFAULT_CIRCUIT);
+        assert_eq!(oil.state, SenderState::NoReference);
+    }
+
+    #[test]
+    fn min_max_tracks_extremes() {
+        let mut range = MinMax::new(150.0);
+        for value in [180.0, 140.0, 160.0] {
+            range.update(value);
+        }
+        assert_eq!(
+            range,
+            MinMax {
+                min: 140.0,
+                max: 180.0
+            }
+        );
+    }
+}
@@ -0,0 +1,569 @@
+//! Calibration that lives in flash instead of in the source.
+//!
+//! Settings are stored as fixed-layout records in two slots, written
+//! alternately. Each record carries a version, a sequence number and a
+//! CRC-32; on boot the newest record that checks out wins. A power cut
+//! mid-write can only tear the slot being written, leaving the other one
+//! intact, and with neither slot valid the compiled defaults are used.
+//!
+//! Where the slots live and how they are erased and programmed is up to the
+//! firmware; everything here works on plain byte slices.
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use crate::{
+    curve::Curve,
+    filter::SloshConfig,
+    sensors::{
+        Circuit, FuelCurve, OilPressureCurve, DEFAULT_CIRCUIT, DEFAULT_FUEL_CURVE,
+        DEFAULT_OIL_CURVE,
+    },
+    speed::SpeedConfig,
+    tach::TachConfig,
+    thermistor::{SteinhartHart, Thermistor, DEFAULT_THERMISTOR},
+    warnings::{Direction, Threshold, WarningConfig},
+    GaugeConfig,
+};
+
+/// Bump when the record layout changes. Records of any other version are
+/// ignored, so a firmware update with a new layout starts from defaults.
+pub const SETTINGS_VERSION: u16 = 2;
+
+const MAGIC: [u8; 4] = *b"HBST";
+/// Magic, version, payload length, sequence
+const HEADER_LEN: usize = 4 + 2 + 2 + 4;
+const PAYLOAD_LEN: usize = 245;
+const CRC_LEN: usize = 4;
+
+/// Bytes one record takes in a slot.
+pub const RECORD_LEN: usize = HEADER_LEN + PAYLOAD_LEN + CRC_LEN;
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// Everything that used to be a `const` next to the code that used it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    pub circuit: Circuit,
+    pub thermistor: Thermistor,
+    pub fuel_curve: FuelCurve,
+    pub oil_curve: OilPressureCurve,
+    pub slosh: SloshConfig,
+    pub warnings: WarningConfig,
+    pub tach: TachConfig,
+    pub speed: SpeedConfig,
+    pub gauges: GaugeConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            circuit: DEFAULT_CIRCUIT,
+            thermistor: DEFAULT_THERMISTOR,
+            fuel_curve: DEFAULT_FUEL_CURVE,
+            oil_curve: DEFAULT_OIL_CURVE,
+            slosh: SloshConfig::default(),
+            warnings: WarningConfig::default(),
+            tach: TachConfig::default(),
+            speed: SpeedConfig::default(),
+            gauges: GaugeConfig::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Values that would divide by zero or draw nonsense. Checked on load so
+    /// a record that passes its CRC but came from a bad edit still falls
+    /// back to defaults.
+    pub(crate) fn is_sane(&self) -> bool {
+        let c = &self.circuit;
+        c.pull_up_ohms > 0.0
+            && (0.0..1.0).contains(&c.pull_up_tolerance)
+            && c.battery_r1_ohms >= 0.0
+            && c.battery_r2_ohms > 0.0
+            && match self.thermistor {
+                Thermistor::Beta { beta, r25 } => beta > 0.0 && r25 > 0.0,
+                Thermistor::SteinhartHart(_) => true,
+            }
+            // A zero time constant makes the filter divide 0 by 0
+            && self.slosh.tau_ms > 0
+            && self.slosh.settle_tau_ms > 0
+            && self.tach.pulses_per_rev > 0.0
+            && self.tach.timeout_us > 0
+            && self.speed.pulses_per_mile > 0
+            && self.speed.timeout_us > 0
+            && self.gauges.temp_max_f > self.gauges.temp_min_f
+    }
+
+    fn write(&self, w: &mut Writer) {
+        let c = &self.circuit;
+        w.f32(c.pull_up_ohms);
+        w.f32(c.pull_up_tolerance);
+        w.f32(c.battery_r1_ohms);
+        w.f32(c.battery_r2_ohms);
+
+        match self.thermistor {
+            Thermistor::Beta { beta, r25 } => {
+                w.u8(0);
+                w.f32(beta);
+                w.f32(r25);
+                w.f32(0.0);
+            }
+            Thermistor::SteinhartHart(SteinhartHart { a, b, c }) => {
+                w.u8(1);
+                w.f32(a);
+                w.f32(b);
+                w.f32(c);
+            }
+        }
+
+        w.curve(&self.fuel_curve);
+        w.curve(&self.oil_curve);
+
+        let s = &self.slosh;
+        w.u32(s.tau_ms);
+        w.u32(s.settle_tau_ms);
+        w.u32(s.settle_ms);
+        w.f32(s.refuel_step);
+        w.u32(s.refuel_hold_ms);
+
+        let t = &self.warnings;
+        for threshold in [t.low_oil_pressure, t.overheat, t.low_voltage, t.low_fuel] {
+            w.u8(match threshold.direction {
+                Direction::Above => 0,
+                Direction::Below => 1,
+            });
+            w.f32(threshold.trip);
+            w.f32(threshold.clear);
+            w.u32(threshold.hold_ms);
+        }
+
+        w.f32(self.tach.pulses_per_rev);
+        w.u32(self.tach.min_period_us);
+        w.u32(self.tach.timeout_us);
+
+        w.u32(self.speed.pulses_per_mile);
+        w.u32(self.speed.min_period_us);
+        w.u32(self.speed.timeout_us);
+
+        w.f32(self.gauges.temp_min_f);
+        w.f32(self.gauges.temp_max_f);
+    }
+
+    fn read(r: &mut Reader) -> Option<Self> {
+        let circuit = Circuit {
+            pull_up_ohms: r.f32()?,
+            pull_up_tolerance: r.f32()?,
+            battery_r1_ohms: r.f32()?,
+            battery_r2_ohms: r.f32()?,
+        };
+
+        let tag = r.u8()?;
+        let (k0, k1, k2) = (r.f32()?, r.f32()?, r.f32()?);
+        let thermistor = match tag {
+            0 => Thermistor::Beta { beta: k0, r25: k1 },
+            1 => Thermistor::SteinhartHart(SteinhartHart {
+                a: k0,
+                b: k1,
+                c: k2,
+            }),
+            _ => return None,
+        };
+
+        let fuel_curve = r.curve()?;
+        let oil_curve = r.curve()?;
+
+        let slosh = SloshConfig {
+            tau_ms: r.u32()?,
+            settle_tau_ms: r.u32()?,
+            settle_ms: r.u32()?,
+            refuel_step: r.f32()?,
+            refuel_hold_ms: r.u32()?,
+        };
+
+        let mut threshold = || {
+            let direction = match r.u8()? {
+                0 => Direction::Above,
+                1 => Direction::Below,
+                _ => return None,
+            };
+            Some(Threshold {
+                direction,
+                trip: r.f32()?,
+                clear: r.f32()?,
+                hold_ms: r.u32()?,
+            })
+        };
+        let warnings = WarningConfig {
+            low_oil_pressure: threshold()?,
+            overheat: threshold()?,
+            low_voltage: threshold()?,
+            low_fuel: threshold()?,
+        };
+
+        let tach = TachConfig {
+            pulses_per_rev: r.f32()?,
+            min_period_us: r.u32()?,
+            timeout_us: r.u32()?,
+        };
+        let speed = SpeedConfig {
+            pulses_per_mile: r.u32()?,
+            min_period_us: r.u32()?,
+            timeout_us: r.u32()?,
+        };
+        let gauges = GaugeConfig {
+            temp_min_f: r.f32()?,
+            temp_max_f: r.f32()?,
+        };
+
+        Some(Self {
+            circuit,
+            thermistor,
+            fuel_curve,
+            oil_curve,
+            slosh,
+            warnings,
+            tach,
+            speed,
+            gauges,
+        })
+    }
+
+    /// One complete record, ready to program into a slot.
+    pub fn encode(&self, sequence: u32) -> [u8; RECORD_LEN] {
+        let mut record = [0; RECORD_LEN];
+        let mut w = Writer {
+            buf: &mut record,
+            pos: 0,
+        };
+        w.bytes(&MAGIC);
+        w.u16(SETTINGS_VERSION);
+        w.u16(PAYLOAD_LEN as u16);
+        w.u32(sequence);
+        self.write(&mut w);
+        debug_assert_eq!(w.pos, HEADER_LEN + PAYLOAD_LEN);
+
+        let crc = CRC32.checksum(&record[..HEADER_LEN + PAYLOAD_LEN]);
+        record[HEADER_LEN + PAYLOAD_LEN..].copy_from_slice(&crc.to_le_bytes());
+        record
+    }
+
+    /// Parses a record from the start of `slot`, returning its sequence
+    /// number. `None` for erased flash, a torn write, a bad CRC or another
+    /// layout version.
+    pub fn decode(slot: &[u8]) -> Option<(u32, Self)> {
+        let record = slot.get(..RECORD_LEN)?;
+        let (body, crc) = record.split_at(HEADER_LEN + PAYLOAD_LEN);
+        if CRC32.checksum(body).to_le_bytes() != crc {
+            return None;
+        }
+
+        let mut r = Reader { buf: body, pos: 0 };
+        if r.bytes(4)? != MAGIC || r.u16()? != SETTINGS_VERSION || r.u16()? as usize != PAYLOAD_LEN
+        {
+            return None;
+        }
+        let sequence = r.u32()?;
+        let settings = Self::read(&mut r)?;
+        settings.is_sane().then_some((sequence, settings))
+    }
+}
+
+/// The two places a record can live.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Slot {
+    A,
+    B,
+}
+
+impl Slot {
+    pub fn other(self) -> Self {
+        match self {
+            Slot::A => Slot::B,
+            Slot::B => Slot::A,
+        }
+    }
+}
+
+/// A record to program, from [`SettingsStore::prepare`].
+pub struct PendingWrite {
+    pub slot: Slot,
+    pub sequence: u32,
+    pub record: [u8; RECORD_LEN],
+}
+
+/// Tracks which slot holds the newest good record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SettingsStore {
+    newest: Option<(Slot, u32)>,
+}
+
+impl SettingsStore {
+    /// Picks the newest valid record from the two slots, or the compiled
+    /// defaults if neither is valid.
+    pub fn load(slot_a: &[u8], slot_b: &[u8]) -> (Self, Settings) {
+        let a = Settings::decode(slot_a).map(|(seq, s)| (Slot::A, seq, s));
+        let b = Settings::decode(slot_b).map(|(seq, s)| (Slot::B, seq, s));
+        let newest = match (a, b) {
+            (Some(a), Some(b)) => Some(if is_newer(b.1, a.1) { b } else { a }),
+            (a, b) => a.or(b),
+        };
+        match newest {
+            Some((slot, seq, settings)) => (
+                Self {
+                    newest: Some((slot, seq)),
+                },
+                settings,
+            ),
+            None => (Self { newest: None }, Settings::default()),
+        }
+    }
+
+    /// Slot and sequence of the record in use, `None` when running on
+    /// defaults.
+    pub fn newest(&self) -> Option<(Slot, u32)> {
+        self.newest
+    }
+
+    /// Encodes `settings` for the slot not holding the newest record.
+    ///
+    /// Program and verify it, then call [`Self::commit`]. Until then the
+    /// store keeps pointing at the old record, so a failed write is never
+    /// followed by one that overwrites the last good copy.
+    pub fn prepare(&self, settings: &Settings) -> PendingWrite {
+        let (slot, sequence) = match self.newest {
+            Some((slot, seq)) => (slot.other(), seq.wrapping_add(1)),
+            None => (Slot::A, 0),
+        };
+        PendingWrite {
+            slot,
+            sequence,
+            record: settings.encode(sequence),
+        }
+    }
+
+    pub fn commit(&mut self, written: &PendingWrite) {
+        self.newest = Some((written.slot, written.sequence));
+    }
+}
+
+/// Sequence numbers wrap, so compare them the way TCP does.
+pub(crate) fn is_newer(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) > 0
+}
+
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buf[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+    }
+
+    fn u8(&mut self, v: u8) {
+        self.bytes(&[v]);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    fn curve<const N: usize>(&mut self, curve: &Curve<N>) {
+        for p in curve.points() {
+            self.f32(p.x);
+            self.f32(p.y);
+        }
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.buf.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.bytes(N)?.try_into().ok()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        self.array().map(f32::from_le_bytes)
+    }
+
+    fn curve<const N: usize>(&mut self) -> Option<Curve<N>> {
+        let mut pairs = [(0.0, 0.0); N];
+        for pair in &mut pairs {
+            *pair = (self.f32()?, self.f32()?);
+        }
+        Curve::try_new(pairs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ERASED: [u8; RECORD_LEN] = [0xFF; RECORD_LEN];
+
+    fn tuned() -> Settings {
+        let mut settings = Settings::default();
+        settings.circuit.pull_up_ohms = 2_200.0;
+        settings.circuit.pull_up_tolerance = 0.05;
+        settings.thermistor = Thermistor::SteinhartHart(SteinhartHart {
+            a: 1.1e-3,
+            b: 2.4e-4,
+            c: 7.5e-8,
+        });
+        settings.warnings.overheat = Threshold::above(215.0, 210.0, 1_000);
+        settings.speed.pulses_per_mile = 3_756;
+        settings.gauges.temp_max_f = 260.0;
+        settings
+    }
+
+    #[test]
+    fn round_trips() {
+        let record = tuned().encode(7);
+        assert_eq!(Settings::decode(&record), Some((7, tuned())));
+    }
+
+    #[test]
+    fn erased_flash_loads_defaults() {
+        let (store, settings) = SettingsStore::load(&ERASED, &ERASED);
+        assert_eq!(settings, Settings::default());
+        assert_eq!(store.newest(), None);
+    }
+
+    #[test]
+    fn alternates_slots() {
+        let mut slots = [ERASED; 2];
+        let (mut store, _) = SettingsStore::load(&slots[0], &slots[1]);
+        for (i, expected) in [Slot::A, Slot::B, Slot::A].into_iter().enumerate() {
+            let mut settings = tuned();
+            settings.gauges.temp_min_f = i as f32;
+            let pending = store.prepare(&settings);
+            assert_eq!(pending.slot, expected);
+            slots[pending.slot as usize] = pending.record;
+            store.commit(&pending);
+
+            let (reloaded, loaded) = SettingsStore::load(&slots[0], &slots[1]);
+            assert_eq!(loaded, settings);
+            assert_eq!(reloaded, store);
+        }
+    }
+
+    #[test]
+    fn torn_write_keeps_previous_record() {
+        let old = Settings::default().encode(41);
+        // Power lost half way through programming the other slot
+        let mut torn = ERASED;
+        let new = tuned().encode(42);
+        torn[..RECORD_LEN / 2].copy_from_slice(&new[..RECORD_LEN / 2]);
+
+        let (store, settings) = SettingsStore::load(&old, &torn);
+        assert_eq!(settings, Settings::default());
+        assert_eq!(store.newest(), Some((Slot::A, 41)));
+        // ...and the retry goes to the torn slot, not over the good one
+        assert_eq!(store.prepare(&tuned()).slot, Slot::B);
+    }
+
+    #[test]
+    fn flipped_bit_is_rejected() {
+        let mut record = tuned().encode(3);
+        record[HEADER_LEN + 5] ^= 0x10;
+        assert_eq!(Settings::decode(&record), None);
+        let (_, settings) = SettingsStore::load(&record, &ERASED);
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn other_versions_are_ignored() {
+        let mut record = tuned().encode(3);
+        record[4..6].copy_from_slice(&(SETTINGS_VERSION + 1).to_le_bytes());
+        let crc = CRC32.checksum(&record[..HEADER_LEN + PAYLOAD_LEN]);
+        record[HEADER_LEN + PAYLOAD_LEN..].copy_from_slice(&crc.to_le_bytes());
+        assert_eq!(Settings::decode(&record), None);
+    }
+
+    #[test]
+    fn insane_values_are_rejected() {
+        let mut settings = tuned();
+        settings.circuit.battery_r2_ohms = 0.0;
+        assert_eq!(Settings::decode(&settings.encode(0)), None);
+
+        let mut settings = tuned();
+        settings.circuit.pull_up_tolerance = 1.0;
+        assert_eq!(Settings::decode(&settings.encode(0)), None);
+    }
+
+    #[test]
+    fn slosh_time_constants_must_be_positive() {
+        let mut settings = tuned();
+        settings.slosh.tau_ms = 0;
+        assert!(!settings.is_sane());
+        assert_eq!(Settings::decode(&settings.encode(0)), None);
+
+        let mut settings = tuned();
+        settings.slosh.settle_tau_ms = 0;
+        assert!(!settings.is_sane());
+    }
+
+    #[test]
+    fn beta_thermistor_must_be_positive() {
+        for (beta, r25) in [(0.0, 325.0), (-3_962.0, 325.0), (3_962.0, 0.0)] {
+            let settings = Settings {
+                thermistor: Thermistor::Beta { beta, r25 },
+                ..Settings::default()
+            };
+            assert!(!settings.is_sane(), "{} {}", beta, r25);
+        }
+    }
+
+    #[test]
+    fn pulse_timeouts_must_be_positive() {
+        let mut settings = tuned();
+        settings.tach.timeout_us = 0;
+        assert!(!settings.is_sane());
+
+        let mut settings = tuned();
+        settings.speed.timeout_us = 0;
+        assert!(!settings.is_sane());
+        assert_eq!(Settings::decode(&settings.encode(0)), None);
+    }
+
+    #[test]
+    fn sequence_wraps() {
+        let a = Settings::default().encode(u32::MAX);
+        let b = tuned().encode(0);
+        let (store, settings) = SettingsStore::load(&a, &b);
+        assert_eq!(settings, tuned());
+        assert_eq!(store.newest(), Some((Slot::B, 0)));
+    }
+}
@@ -0,0 +1,178 @@
+//! Road speed from the vehicle speed sensor (VSS) pulse train.
+//!
+//! Speed is averaged from pulse periods like the tach. Every accepted pulse
+//! is also counted so the firmware can hand whole pulses to the
+//! [`Odometer`](crate::Odometer); distance never goes through floats.
+
+use crate::pulse::PulseTimer;
+
+/// Tuning for [`Speedometer`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpeedConfig {
+    /// VSS pulses per mile travelled on the fitted tires.
+    pub pulses_per_mile: u32,
+    /// Shorter periods than this are noise, not pulses (µs).
+    pub min_period_us: u32,
+    /// With no pulse for this long the truck is stopped (µs).
+    pub timeout_us: u32,
+}
+
+impl Default for SpeedConfig {
+    fn default() -> Self {
+        Self {
+            // 4 pulses per speedo cable turn, 1000 turns per mile
+            pulses_per_mile: 4_000,
+            // 4000 ppm at 450 mph, well clear of anything real
+            min_period_us: 2_000,
+            // Under ~1 mph reads as stopped
+            timeout_us: 1_000_000,
+        }
+    }
+}
+
+impl SpeedConfig {
+    /// Rescales `pulses_per_mile` for tires of a different rolling diameter
+    /// than the ones it was measured on. Any unit works for the diameters as
+    /// long as both use the same one.
+    pub fn with_tire(self, measured_diameter: u32, fitted_diameter: u32) -> Self {
+        if measured_diameter == 0 || fitted_diameter == 0 {
+            return self;
+        }
+        // A bigger tire covers more road per turn, so fewer pulses per mile.
+        let scaled = (self.pulses_per_mile as u64 * measured_diameter as u64
+            + fitted_diameter as u64 / 2)
+            / fitted_diameter as u64;
+        Self {
+            pulses_per_mile: scaled.clamp(1, u32::MAX as u64) as u32,
+            ..self
+        }
+    }
+}
+
+/// Road speed for one frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SpeedReading {
+    pub mph: f32,
+}
+
+/// Averages the last `N` VSS periods and counts pulses for the odometer.
+#[derive(Clone, Debug)]
+pub struct Speedometer<const N: usize> {
+    config: SpeedConfig,
+    pulses: PulseTimer<N>,
+    /// Accepted pulses not yet collected by [`Self::take_pulses`]
+    uncounted: u32,
+}
+
+impl<const N: usize> Speedometer<N> {
+    pub const fn new(config: SpeedConfig) -> Self {
+        Self {
+            config,
+            pulses: PulseTimer::new(config.min_period_us, config.timeout_us),
+            uncounted: 0,
+        }
+    }
+
+    pub fn config(&self) -> &SpeedConfig {
+        &self.config
+    }
+
+    /// Swaps in new calibration. Averaging restarts, but pulses not yet
+    /// taken stay counted so the odometer doesn't lose them.
+    pub fn configure(&mut self, config: SpeedConfig) {
+        *self = Self {
+            uncounted: self.uncounted,
+            ..Self::new(config)
+        };
+    }
+
+    /// Records an edge at `timestamp_us` on a free-running, wrapping
+    /// microsecond counter.
+    pub fn on_pulse(&mut self, timestamp_us: u32) {
+        if self.pulses.on_edge(timestamp_us) {
+            self.uncounted = self.uncounted.saturating_add(1);
+        }
+    }
+
+    /// Pulses seen since the last call, for [`Odometer::add_pulses`].
+    ///
+    /// [`Odometer::add_pulses`]: crate::Odometer::add_pulses
+    pub fn take_pulses(&mut self) -> u32 {
+        core::mem::take(&mut self.uncounted)
+    }
+
+    /// Road speed as of `now_us`, on the same counter as the pulses.
+    pub fn read(&mut self, now_us: u32) -> SpeedReading {
+        match self.pulses.mean_period_us(now_us) {
+            Some(mean_us) => SpeedReading {
+                mph: 3_600_000_000.0 / (mean_us * self.config.pulses_per_mile as f32),
+            },
+            None => SpeedReading::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Period between pulses at `mph` with the default calibration.
+    fn period_at(mph: f32) -> u32 {
+        (3_600_000_000.0 / (mph * 4_000.0)) as u32
+    }
+
+    fn drive(speedo: &mut Speedometer<8>, start_us: u32, period_us: u32, count: u32) -> u32 {
+        let mut t = start_us;
+        for _ in 0..count {
+            speedo.on_pulse(t);
+            t = t.wrapping_add(period_us);
+        }
+        t.wrapping_sub(period_us)
+    }
+
+    #[test]
+    fn steady_cruise() {
+        let mut speedo = Speedometer::<8>::new(SpeedConfig::default());
+        let last = drive(&mut speedo, 0, period_at(55.0), 30);
+        assert!((speedo.read(last).mph - 55.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn zero_when_stopped() {
+        let mut speedo = Speedometer::<8>::new(SpeedConfig::default());
+        assert_eq!(speedo.read(0).mph, 0.0);
+        let last = drive(&mut speedo, 0, period_at(30.0), 10);
+        assert_eq!(speedo.read(last + 1_500_000).mph, 0.0);
+    }
+
+    #[test]
+    fn counts_accepted_pulses_once() {
+        let mut speedo = Speedometer::<8>::new(SpeedConfig::default());
+        drive(&mut speedo, 0, period_at(40.0), 25);
+        speedo.on_pulse(period_at(40.0) * 24 + 100); // contact bounce
+        assert_eq!(speedo.take_pulses(), 25);
+        assert_eq!(speedo.take_pulses(), 0);
+    }
+
+    #[test]
+    fn reconfiguring_keeps_untaken_pulses() {
+        let mut speedo = Speedometer::<8>::new(SpeedConfig::default());
+        let last = drive(&mut speedo, 0, period_at(40.0), 12);
+        speedo.configure(SpeedConfig::default().with_tire(739, 787));
+        assert_eq!(speedo.read(last).mph, 0.0);
+        assert_eq!(speedo.config().pulses_per_mile, 3_756);
+        assert_eq!(speedo.take_pulses(), 12);
+    }
+
+    #[test]
+    fn bigger_tires_mean_fewer_pulses_per_mile() {
+        // 215/75R15 stock, 31x10.50R15 fitted
+        let config = SpeedConfig::default().with_tire(739, 787);
+        assert_eq!(config.pulses_per_mile, 3_756);
+
+        assert_eq!(
+            SpeedConfig::default().with_tire(0, 787),
+            SpeedConfig::default()
+        );
+    }
+}
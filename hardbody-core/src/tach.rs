@@ -0,0 +1,159 @@
+//! Engine speed from ignition coil or alternator W-terminal pulses.
+//!
+//! The firmware timestamps each falling edge in an interrupt and hands it to
+//! [`Tachometer::on_pulse`]; everything else here is plain arithmetic on
+//! those timestamps, so it runs the same on the host.
+
+use crate::pulse::PulseTimer;
+
+/// Tuning for [`Tachometer`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TachConfig {
+    /// Pulses per crank revolution: 2 for a 4-cylinder coil, or pole pairs ×
+    /// pulley ratio for the alternator W terminal.
+    pub pulses_per_rev: f32,
+    /// Shorter periods than this are ignition noise, not pulses (µs).
+    pub min_period_us: u32,
+    /// With no pulse for this long the engine is stopped (µs).
+    pub timeout_us: u32,
+}
+
+impl Default for TachConfig {
+    fn default() -> Self {
+        Self {
+            pulses_per_rev: 2.0,
+            // 2 ppr at 10,000 rpm
+            min_period_us: 3_000,
+            timeout_us: 500_000,
+        }
+    }
+}
+
+/// Engine speed for one frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TachReading {
+    pub rpm: f32,
+}
+
+/// Averages the last `N` pulse periods.
+#[derive(Clone, Debug)]
+pub struct Tachometer<const N: usize> {
+    config: TachConfig,
+    pulses: PulseTimer<N>,
+}
+
+impl<const N: usize> Tachometer<N> {
+    pub const fn new(config: TachConfig) -> Self {
+        Self {
+            config,
+            pulses: PulseTimer::new(config.min_period_us, config.timeout_us),
+        }
+    }
+
+    pub fn config(&self) -> &TachConfig {
+        &self.config
+    }
+
+    /// Records an edge at `timestamp_us` on a free-running, wrapping
+    /// microsecond counter.
+    pub fn on_pulse(&mut self, timestamp_us: u32) {
+        self.pulses.on_edge(timestamp_us);
+    }
+
+    /// Engine speed as of `now_us`, on the same counter as the pulses.
+    pub fn read(&mut self, now_us: u32) -> TachReading {
+        match self.pulses.mean_period_us(now_us) {
+            Some(mean_us) => TachReading {
+                rpm: 60_000_000.0 / (mean_us * self.config.pulses_per_rev),
+            },
+            None => TachReading::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `count` pulses `period_us` apart starting at `start_us`, returning
+    /// the timestamp of the last one.
+    fn pulses(tach: &mut Tachometer<8>, start_us: u32, period_us: u32, count: u32) -> u32 {
+        let mut t = start_us;
+        for _ in 0..count {
+            tach.on_pulse(t);
+            t = t.wrapping_add(period_us);
+        }
+        t.wrapping_sub(period_us)
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1.0
+    }
+
+    #[test]
+    fn steady_idle() {
+        let mut tach = Tachometer::<8>::new(TachConfig::default());
+        // 2 ppr at 800 rpm → 37.5 ms
+        let last = pulses(&mut tach, 0, 37_500, 20);
+        assert!(close(tach.read(last + 1_000).rpm, 800.0));
+    }
+
+    #[test]
+    fn pulses_per_rev_scales() {
+        let config = TachConfig {
+            pulses_per_rev: 6.5, // alternator W terminal
+            min_period_us: 500,
+            ..TachConfig::default()
+        };
+        let mut tach = Tachometer::<8>::new(config);
+        let period = (60_000_000.0 / (3_000.0 * 6.5)) as u32;
+        let last = pulses(&mut tach, 0, period, 20);
+        assert!((tach.read(last).rpm - 3_000.0).abs() < 5.0);
+    }
+
+    #[test]
+    fn averages_recent_periods() {
+        let mut tach = Tachometer::<8>::new(TachConfig::default());
+        let t = pulses(&mut tach, 0, 20_000, 9); // 1500 rpm
+        let last = pulses(&mut tach, t + 10_000, 10_000, 4); // 3000 rpm
+
+        // 4 short periods + 4 long ones in the window
+        assert!(close(tach.read(last).rpm, 2_000.0), "{:?}", tach.read(last));
+    }
+
+    #[test]
+    fn zero_before_first_period_and_after_stall() {
+        let mut tach = Tachometer::<8>::new(TachConfig::default());
+        assert_eq!(tach.read(0).rpm, 0.0);
+        tach.on_pulse(1_000);
+        assert_eq!(tach.read(2_000).rpm, 0.0);
+
+        let last = pulses(&mut tach, 20_000, 20_000, 10);
+        assert!(tach.read(last).rpm > 0.0);
+        assert_eq!(tach.read(last + 600_000).rpm, 0.0);
+
+        // Restart: the stall gap must not drag the average down
+        let last = pulses(&mut tach, last + 2_000_000, 30_000, 3);
+        assert!(close(tach.read(last).rpm, 1_000.0));
+    }
+
+    #[test]
+    fn ignores_noise_between_pulses() {
+        let mut tach = Tachometer::<8>::new(TachConfig::default());
+        let mut t = 0;
+        for _ in 0..10 {
+            tach.on_pulse(t);
+            tach.on_pulse(t + 200); // ringing on the coil
+            t += 30_000;
+        }
+        assert!(close(tach.read(t - 30_000).rpm, 1_000.0));
+    }
+
+    #[test]
+    fn survives_counter_wrap() {
+        let mut tach = Tachometer::<8>::new(TachConfig::default());
+        let last = pulses(&mut tach, u32::MAX - 50_000, 20_000, 8);
+        assert!(last < 200_000);
+        assert!(close(tach.read(last).rpm, 1_500.0));
+    }
+}
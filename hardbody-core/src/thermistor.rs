@@ -0,0 +1,161 @@
+//! NTC thermistor models: resistance → temperature.
+
+// Called as `F32Ext::ln(x)` rather than `x.ln()` so host test builds, where
+// std's inherent float methods are in scope, use the same approximation as
+// the firmware.
+use micromath::F32Ext;
+
+const T25_K: f32 = 298.15;
+
+fn f_to_k(f: f32) -> f32 {
+    (f - 32.0) / 1.8 + 273.15
+}
+
+/// Steinhart–Hart coefficients: `1/T = A + B·ln(R) + C·ln(R)³`, T in kelvin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SteinhartHart {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+}
+
+impl SteinhartHart {
+    /// Solves A/B/C from three measured `(ohms, °F)` points.
+    ///
+    /// Spread the points over the range you care about (e.g. ambient, normal
+    /// running, boiling). Returns `None` if two points share a resistance or
+    /// any resistance isn't positive.
+    pub fn from_points(points: [(f32, f32); 3]) -> Option<Self> {
+        if points.iter().any(|&(r, _)| r.is_nan() || r <= 0.0) {
+            return None;
+        }
+        // The solve subtracts nearly equal values, so do it in f64.
+        let l = points.map(|(r, _)| F32Ext::ln(r) as f64);
+        let y = points.map(|(_, f)| 1.0 / f_to_k(f) as f64);
+        if l[0] == l[1] || l[1] == l[2] || l[0] == l[2] {
+            return None;
+        }
+
+        let g2 = (y[1] - y[0]) / (l[1] - l[0]);
+        let g3 = (y[2] - y[0]) / (l[2] - l[0]);
+        let c = (g3 - g2) / (l[2] - l[1]) / (l[0] + l[1] + l[2]);
+        let b = g2 - c * (l[0] * l[0] + l[0] * l[1] + l[1] * l[1]);
+        let a = y[0] - (b + l[0] * l[0] * c) * l[0];
+
+        Some(Self {
+            a: a as f32,
+            b: b as f32,
+            c: c as f32,
+        })
+    }
+}
+
+/// How the coolant channel turns thermistor resistance into temperature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Thermistor {
+    /// `1/T = 1/T25 + ln(R/R25)/β`; good near 25 °C, drifts at the ends.
+    Beta { beta: f32, r25: f32 },
+    /// Full three-coefficient fit.
+    SteinhartHart(SteinhartHart),
+}
+
+/// The stock coolant sender as originally fitted.
+pub const DEFAULT_THERMISTOR: Thermistor = Thermistor::Beta {
+    beta: 3962.0,
+    r25: 325.0,
+};
+
+impl Thermistor {
+    pub fn kelvin(&self, ohms: f32) -> f32 {
+        let inv_t = match *self {
+            // micromath's ln is far off below 1, so never take ln(R/R25)
+            // directly; a hot sender is well under R25.
+            Thermistor::Beta { beta, r25 } => {
+                1.0 / T25_K + (F32Ext::ln(ohms) - F32Ext::ln(r25)) / beta
+            }
+            Thermistor::SteinhartHart(SteinhartHart { a, b, c }) => {
+                let l = F32Ext::ln(ohms);
+                a + b * l + c * l * l * l
+            }
+        };
+        1.0 / inv_t
+    }
+
+    /// The same curve as Steinhart–Hart coefficients. A Beta model is the
+    /// special case `C = 0`, so the conversion is exact.
+    pub fn steinhart_hart(&self) -> SteinhartHart {
+        match *self {
+            Thermistor::Beta { beta, r25 } => SteinhartHart {
+                a: 1.0 / T25_K - F32Ext::ln(r25) / beta,
+                b: 1.0 / beta,
+                c: 0.0,
+            },
+            Thermistor::SteinhartHart(sh) => sh,
+        }
+    }
+
+    pub fn fahrenheit(&self, ohms: f32) -> f32 {
+        (self.kelvin(ohms) - 273.15) * 1.8 + 32.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32, tol: f32) -> bool {
+        (a - b).abs() <= tol
+    }
+
+    #[test]
+    fn beta_at_r25_is_77f() {
+        assert!(close(DEFAULT_THERMISTOR.fahrenheit(325.0), 77.0, 0.5));
+    }
+
+    #[test]
+    fn beta_when_hot() {
+        // 50 Ω is ~165 °F on the stock sender
+        assert!(close(DEFAULT_THERMISTOR.fahrenheit(50.0), 165.0, 0.5));
+    }
+
+    #[test]
+    fn steinhart_hart_passes_through_its_points() {
+        // Cold start, warm-up and overheat
+        let points = [(3_520.0, 32.0), (185.0, 160.0), (47.0, 240.0)];
+        let sh = Thermistor::SteinhartHart(SteinhartHart::from_points(points).unwrap());
+        for (ohms, f) in points {
+            assert!(close(sh.fahrenheit(ohms), f, 0.5), "{} Ω", ohms);
+        }
+    }
+
+    #[test]
+    fn steinhart_hart_reproduces_a_beta_curve() {
+        let beta = DEFAULT_THERMISTOR;
+        let at = |f: f32| {
+            // Invert the Beta model to get the resistance at `f`
+            let Thermistor::Beta { beta, r25 } = beta else {
+                unreachable!()
+            };
+            r25 * F32Ext::exp(beta * (1.0 / f_to_k(f) - 1.0 / T25_K))
+        };
+        let sh = SteinhartHart::from_points([
+            (at(100.0), 100.0),
+            (at(190.0), 190.0),
+            (at(260.0), 260.0),
+        ])
+        .map(Thermistor::SteinhartHart)
+        .unwrap();
+        for f in (120..=270).step_by(10) {
+            let f = f as f32;
+            assert!(close(sh.fahrenheit(at(f)), f, 1.0), "{} °F", f);
+        }
+    }
+
+    #[test]
+    fn rejects_degenerate_points() {
+        assert!(
+            SteinhartHart::from_points([(100.0, 150.0), (100.0, 160.0), (50.0, 200.0)]).is_none()
+        );
+        assert!(SteinhartHart::from_points([(0.0, 150.0), (80.0, 160.0), (50.0, 200.0)]).is_none());
+    }
+}
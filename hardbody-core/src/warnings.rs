@@ -0,0 +1,467 @@
+//! Threshold warnings with hysteresis and debounce.
+//!
+//! Each alert trips once its value has been past `trip` for `hold_ms`, then
+//! stays latched until the value comes back past `clear`. Keeping `clear`
+//! a little inside `trip` stops an alert chattering on the boundary.
+//!
+//! A sender with broken wiring raises its own alert instead of the one its
+//! value would, since a shorted thermistor reads as boiling and an open
+//! fuel sender as empty. A 3.3V rail out of its window raises
+//! [`Alert::Reference`] and takes every ratiometric value with it.
+
+use crate::{
+    pages::Page,
+    sensors::{RailState, SenderState},
+};
+
+/// Things worth shouting about, highest priority first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Alert {
+    LowOilPressure,
+    Overheat,
+    Reference,
+    OilSender,
+    CoolantSender,
+    LowVoltage,
+    LowFuel,
+    FuelSender,
+}
+
+impl Alert {
+    pub const ALL: [Alert; 8] = [
+        Alert::LowOilPressure,
+        Alert::Overheat,
+        Alert::Reference,
+        Alert::OilSender,
+        Alert::CoolantSender,
+        Alert::LowVoltage,
+        Alert::LowFuel,
+        Alert::FuelSender,
+    ];
+
+    /// Short enough to fit the 128 px panel in `FONT_10X20`.
+    pub fn message(self) -> &'static str {
+        match self {
+            Alert::LowOilPressure => "OIL PRESS",
+            Alert::Overheat => "OVERHEAT",
+            Alert::LowVoltage => "LOW VOLTS",
+            Alert::LowFuel => "LOW FUEL",
+            Alert::Reference => "REF VOLTS",
+            Alert::OilSender => "OIL SENDER",
+            Alert::CoolantSender => "TEMP SENDER",
+            Alert::FuelSender => "FUEL SENDER",
+        }
+    }
+
+    /// The screen that shows the value this alert is about.
+    pub fn page(self) -> Page {
+        match self {
+            Alert::LowOilPressure | Alert::OilSender => Page::OilPressure,
+            // The coolant screen used to show the rail, so look there
+            Alert::Overheat | Alert::Reference | Alert::CoolantSender => Page::Coolant,
+            Alert::LowVoltage | Alert::LowFuel | Alert::FuelSender => Page::Fuel,
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Which side of the limit is bad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Above,
+    Below,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Threshold {
+    pub direction: Direction,
+    /// Value that trips the alert
+    pub trip: f32,
+    /// Value the reading must return past to clear it
+    pub clear: f32,
+    /// How long the value must stay past `trip`, in milliseconds
+    pub hold_ms: u32,
+}
+
+impl Threshold {
+    pub const fn above(trip: f32, clear: f32, hold_ms: u32) -> Self {
+        Self {
+            direction: Direction::Above,
+            trip,
+            clear,
+            hold_ms,
+        }
+    }
+
+    pub const fn below(trip: f32, clear: f32, hold_ms: u32) -> Self {
+        Self {
+            direction: Direction::Below,
+            trip,
+            clear,
+            hold_ms,
+        }
+    }
+
+    fn tripped(&self, value: f32) -> bool {
+        match self.direction {
+            Direction::Above => value >= self.trip,
+            Direction::Below => value <= self.trip,
+        }
+    }
+
+    fn cleared(&self, value: f32) -> bool {
+        match self.direction {
+            Direction::Above => value < self.clear,
+            Direction::Below => value > self.clear,
+        }
+    }
+}
+
+/// A sender or rail fault shows once it has lasted two seconds, so a
+/// connector that's merely loose doesn't flash the panel every bump.
+const WIRING_FAULT: Threshold = Threshold::above(1.0, 0.5, 2_000);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WarningConfig {
+    /// Oil psi, only checked with the engine running
+    pub low_oil_pressure: Threshold,
+    /// Coolant °F
+    pub overheat: Threshold,
+    /// Battery volts
+    pub low_voltage: Threshold,
+    /// Tank percent
+    pub low_fuel: Threshold,
+}
+
+impl Default for WarningConfig {
+    fn default() -> Self {
+        Self {
+            low_oil_pressure: Threshold::below(7.0, 10.0, 1_000),
+            overheat: Threshold::above(230.0, 225.0, 2_000),
+            low_voltage: Threshold::below(11.8, 12.2, 5_000),
+            low_fuel: Threshold::below(10.0, 15.0, 10_000),
+        }
+    }
+}
+
+impl WarningConfig {
+    fn threshold(&self, alert: Alert) -> &Threshold {
+        match alert {
+            Alert::LowOilPressure => &self.low_oil_pressure,
+            Alert::Overheat => &self.overheat,
+            Alert::LowVoltage => &self.low_voltage,
+            Alert::LowFuel => &self.low_fuel,
+            Alert::Reference | Alert::OilSender | Alert::CoolantSender | Alert::FuelSender => {
+                &WIRING_FAULT
+            }
+        }
+    }
+}
+
+/// The values the engine watches, one set per loop iteration.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WarningInputs {
+    pub coolant_f: f32,
+    pub battery_v: f32,
+    pub fuel_pct: f32,
+    pub oil_psi: f32,
+    /// Oil pressure is expected to be near zero with the engine off.
+    pub engine_running: bool,
+    pub coolant_sender: SenderState,
+    pub fuel_sender: SenderState,
+    pub oil_sender: SenderState,
+    /// The 3.3V rail as either ADC sees it
+    pub reference: RailState,
+}
+
+impl WarningInputs {
+    /// The value to check for `alert`, or `None` if it doesn't apply now.
+    fn value(&self, alert: Alert) -> Option<f32> {
+        let fault = |faulted: bool| Some(if faulted { 1.0 } else { 0.0 });
+        match alert {
+            Alert::LowOilPressure => {
+                (self.engine_running && self.oil_sender.is_ok()).then_some(self.oil_psi)
+            }
+            Alert::Overheat => self.coolant_sender.is_ok().then_some(self.coolant_f),
+            Alert::LowVoltage => Some(self.battery_v),
+            Alert::LowFuel => self.fuel_sender.is_ok().then_some(self.fuel_pct),
+            Alert::Reference => fault(!self.reference.is_ok()),
+            Alert::OilSender => fault(self.oil_sender.is_broken()),
+            Alert::CoolantSender => fault(self.coolant_sender.is_broken()),
+            Alert::FuelSender => fault(self.fuel_sender.is_broken()),
+        }
+    }
+}
+
+/// Set of active alerts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Alerts(u8);
+
+impl Alerts {
+    pub fn contains(self, alert: Alert) -> bool {
+        self.0 & alert.bit() != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Active alerts, highest priority first.
+    pub fn iter(self) -> impl Iterator<Item = Alert> {
+        Alert::ALL.into_iter().filter(move |a| self.contains(*a))
+    }
+
+    pub fn highest(self) -> Option<Alert> {
+        self.iter().next()
+    }
+
+    /// Highest-priority alert about one screen.
+    pub fn highest_on(self, page: Page) -> Option<Alert> {
+        self.iter().find(|a| a.page() == page)
+    }
+
+    fn set(&mut self, alert: Alert, on: bool) {
+        if on {
+            self.0 |= alert.bit();
+        } else {
+            self.0 &= !alert.bit();
+        }
+    }
+}
+
+pub struct WarningEngine {
+    config: WarningConfig,
+    active: Alerts,
+    /// Time each alert's value has been past `trip` while not yet active
+    pending_ms: [u32; Alert::ALL.len()],
+}
+
+impl WarningEngine {
+    pub const fn new(config: WarningConfig) -> Self {
+        Self {
+            config,
+            active: Alerts(0),
+            pending_ms: [0; Alert::ALL.len()],
+        }
+    }
+
+    pub fn config(&self) -> &WarningConfig {
+        &self.config
+    }
+
+    pub fn active(&self) -> Alerts {
+        self.active
+    }
+
+    /// Feeds one set of readings taken `dt_ms` after the previous one.
+    pub fn update(&mut self, inputs: &WarningInputs, dt_ms: u32) -> Alerts {
+        for alert in Alert::ALL {
+            let threshold = self.config.threshold(alert);
+            let pending = &mut self.pending_ms[alert as usize];
+            let Some(value) = inputs.value(alert) else {
+                self.active.set(alert, false);
+                *pending = 0;
+                continue;
+            };
+
+            if self.active.contains(alert) {
+                if threshold.cleared(value) {
+                    self.active.set(alert, false);
+                }
+            } else if threshold.tripped(value) {
+                *pending = pending.saturating_add(dt_ms);
+                if *pending >= threshold.hold_ms {
+                    self.active.set(alert, true);
+                    *pending = 0;
+                }
+            } else {
+                *pending = 0;
+            }
+        }
+        self.active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NORMAL: WarningInputs = WarningInputs {
+        coolant_f: 195.0,
+        battery_v: 13.8,
+        fuel_pct: 60.0,
+        oil_psi: 40.0,
+        engine_running: true,
+        coolant_sender: SenderState::Ok,
+        fuel_sender: SenderState::Ok,
+        oil_sender: SenderState::Ok,
+        reference: RailState::Ok,
+    };
+
+    fn run(engine: &mut WarningEngine, inputs: WarningInputs, ms: u32) -> Alerts {
+        let mut alerts = engine.active();
+        for _ in 0..ms / 100 {
+            alerts = engine.update(&inputs, 100);
+        }
+        alerts
+    }
+
+    #[test]
+    fn quiet_when_normal() {
+        let mut engine = WarningEngine::new(WarningConfig::default());
+        assert!(run(&mut engine, NORMAL, 60_000).is_empty());
+    }
+
+    #[test]
+    fn trips_after_hold_time() {
+        let mut engine = WarningEngine::new(WarningConfig::default());
+        let hot = WarningInputs {
+            coolant_f: 235.0,
+            ..NORMAL
+        };
+        assert!(run(&mut engine, hot, 1_900).is_empty());
+        assert_eq!(run(&mut engine, hot, 100).highest(), Some(Alert::Overheat));
+    }
+
+    #[test]
+    fn spikes_are_debounced() {
+        let mut engine = WarningEngine::new(WarningConfig::default());
+        let sag = WarningInputs {
+            battery_v: 10.5, // cranking
+            ..NORMAL
+        };
+        for _ in 0..10 {
+            run(&mut engine, sag, 3_000);
+            run(&mut engine, NORMAL, 100);
+        }
+        assert!(engine.active().is_empty());
+    }
+
+    #[test]
+    fn latches_until_clear_point() {
+        let mut engine = WarningEngine::new(WarningConfig::default());
+        let low = WarningInputs {
+            fuel_pct: 8.0,
+            ..NORMAL
+        };
+        run(&mut engine, low, 10_000);
+        assert!(engine.active().contains(Alert::LowFuel));
+
+        // Sloshing back over the trip point isn't enough
+        let sloshing = WarningInputs {
+            fuel_pct: 12.0,
+            ..NORMAL
+        };
+        assert!(run(&mut engine, sloshing, 60_000).contains(Alert::LowFuel));
+
+        let refuelled = WarningInputs {
+            fuel_pct: 90.0,
+            ..NORMAL
+        };
+        assert!(run(&mut engine, refuelled, 100).is_empty());
+    }
+
+    #[test]
+    fn priority_and_pages() {
+        let mut engine = WarningEngine::new(WarningConfig::default());
+        let everything = WarningInputs {
+            coolant_f: 250.0,
+            battery_v: 11.0,
+            fuel_pct: 2.0,
+            oil_psi: 3.0,
+            ..NORMAL
+        };
+        let alerts = run(&mut engine, everything, 10_000);
+        assert!(alerts.iter().eq([
+            Alert::LowOilPressure,
+            Alert::Overheat,
+            Alert::LowVoltage,
+            Alert::LowFuel
+        ]));
+        assert_eq!(alerts.highest(), Some(Alert::LowOilPressure));
+        assert_eq!(alerts.highest_on(Page::Coolant), Some(Alert::Overheat));
+        assert_eq!(alerts.highest_on(Page::Fuel), Some(Alert::LowVoltage));
+    }
+
+    #[test]
+    fn oil_pressure_only_with_engine_running() {
+        let mut engine = WarningEngine::new(WarningConfig::default());
+        let key_on = WarningInputs {
+            oil_psi: 0.0,
+            engine_running: false,
+            ..NORMAL
+        };
+        assert!(run(&mut engine, key_on, 10_000).is_empty());
+
+        let starving = WarningInputs {
+            oil_psi: 4.0,
+            ..NORMAL
+        };
+        assert!(run(&mut engine, starving, 1_000).contains(Alert::LowOilPressure));
+
+        // Stalling clears it rather than leaving it latched
+        assert!(run(&mut engine, key_on, 100).is_empty());
+    }
+
+    #[test]
+    fn sender_faults_replace_their_value_alerts() {
+        let mut engine = WarningEngine::new(WarningConfig::default());
+        // A shorted thermistor reads as boiling
+        let shorted = WarningInputs {
+            coolant_f: 380.0,
+            coolant_sender: SenderState::Short,
+            ..NORMAL
+        };
+        assert!(run(&mut engine, shorted, 1_900).is_empty());
+        let alerts = run(&mut engine, shorted, 100);
+        assert!(alerts.iter().eq([Alert::CoolantSender]));
+        assert_eq!(alerts.highest_on(Page::Coolant), Some(Alert::CoolantSender));
+
+        // ...and a broken fuel wire as empty
+        let open = WarningInputs {
+            fuel_pct: 0.0,
+            fuel_sender: SenderState::Open,
+            oil_sender: SenderState::Open,
+            ..NORMAL
+        };
+        let alerts = run(&mut engine, open, 60_000);
+        assert!(alerts.iter().eq([Alert::OilSender, Alert::FuelSender]));
+
+        assert!(run(&mut engine, NORMAL, 100).is_empty());
+    }
+
+    #[test]
+    fn bad_rail_is_one_alert_not_three() {
+        let mut engine = WarningEngine::new(WarningConfig::default());
+        let brownout = WarningInputs {
+            coolant_f: 300.0,
+            fuel_pct: 0.0,
+            coolant_sender: SenderState::NoReference,
+            fuel_sender: SenderState::NoReference,
+            oil_sender: SenderState::NoReference,
+            reference: RailState::Low,
+            ..NORMAL
+        };
+        let alerts = run(&mut engine, brownout, 60_000);
+        assert!(alerts.iter().eq([Alert::Reference]), "{:?}", alerts);
+        assert_eq!(alerts.highest_on(Page::Coolant), Some(Alert::Reference));
+        assert!(run(&mut engine, NORMAL, 100).is_empty());
+    }
+
+    #[test]
+    fn thresholds_are_configurable() {
+        let config = WarningConfig {
+            overheat: Threshold::above(210.0, 205.0, 0),
+            ..WarningConfig::default()
+        };
+        let mut engine = WarningEngine::new(config);
+        let warm = WarningInputs {
+            coolant_f: 212.0,
+            ..NORMAL
+        };
+        assert!(engine.update(&warm, 100).contains(Alert::Overheat));
+    }
+}
@@ -0,0 +1,262 @@
+//! Golden-image tests for the gauge screens.
+//!
+//! `MockDisplay` is only 64x64, so each 128x64 screen is captured as a left
+//! and a right half and stitched back together. Pixels that are never drawn
+//! count as off, matching a cleared SSD1306 buffer. Run with
+//! `UPDATE_GOLDEN=1` to rewrite the images after an intentional layout change.
+
+use std::{env, fs, path::PathBuf};
+
+use embedded_graphics::{
+    draw_target::Translated, mock_display::MockDisplay, pixelcolor::BinaryColor, prelude::*,
+};
+
+use hardbody_core::{
+    draw_boot_screen, draw_crash_report, draw_fault, draw_fuel_gauge, draw_oil_pressure_gauge,
+    draw_speed_gauge, draw_tach_gauge, draw_temp_gauge, draw_trip_page, draw_warning, Alert,
+    BatteryReading, CoolantReading, Device, Distance, FuelReading, GaugeConfig, MinMax, Odometer,
+    OilPressureReading, ResetLog, ResetReason, SenderState, SpeedReading, TachReading, Trip,
+};
+
+const WIDTH: i32 = 128;
+const HEIGHT: i32 = 64;
+const HALF: i32 = 64;
+
+fn snapshot<F>(draw: F) -> String
+where
+    F: Fn(&mut Translated<'_, MockDisplay<BinaryColor>>),
+{
+    let halves = [0, HALF].map(|offset| {
+        let mut display = MockDisplay::new();
+        display.set_allow_overdraw(true);
+        display.set_allow_out_of_bounds_drawing(true);
+        draw(&mut display.translated(Point::new(-offset, 0)));
+        display
+    });
+
+    let mut out = String::new();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let half = &halves[(x / HALF) as usize];
+            let on = half.get_pixel(Point::new(x % HALF, y)) == Some(BinaryColor::On);
+            out.push(if on { '#' } else { '.' });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn assert_golden(name: &str, actual: &str) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden", name]
+        .iter()
+        .collect::<PathBuf>()
+        .with_extension("txt");
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_GOLDEN=1)", path.display(), e));
+    if expected != actual {
+        let mut diff = String::new();
+        for (y, (a, e)) in actual.lines().zip(expected.lines()).enumerate() {
+            if a != e {
+                diff += &format!("row {:2} actual   {}\n", y, a);
+                diff += &format!("       expected {}\n", e);
+            }
+        }
+        panic!("{} differs from golden image:\n{}", name, diff);
+    }
+}
+
+fn fuel(name: &str, percent: u8) {
+    fuel_with_warning(name, percent, SenderState::Ok, None);
+}
+
+fn fuel_with_warning(name: &str, percent: u8, state: SenderState, alert: Option<Alert>) {
+    let fuel = FuelReading {
+        ratio: 0.0,
+        ohms: 0.0,
+        percent,
+        bounds: MinMax::new(percent as f32),
+        state,
+    };
+    let battery = BatteryReading { volts: 12.6 };
+    let image = snapshot(|d| {
+        draw_fuel_gauge(d, fuel, battery).unwrap();
+        if let Some(alert) = alert {
+            draw_warning(d, alert).unwrap();
+        }
+    });
+    assert_golden(name, &image);
+}
+
+fn temp(name: &str, fahrenheit: f32) {
+    temp_with_state(name, fahrenheit, SenderState::Ok, None);
+}
+
+fn temp_with_state(name: &str, fahrenheit: f32, state: SenderState, range: Option<MinMax>) {
+    let coolant = CoolantReading {
+        ohms: 0.0,
+        fahrenheit,
+        bounds: MinMax::new(fahrenheit),
+        state,
+    };
+    let image = snapshot(|d| draw_temp_gauge(d, coolant, range, &GaugeConfig::default()).unwrap());
+    assert_golden(name, &image);
+}
+
+fn oil(name: &str, psi: f32) {
+    let oil = OilPressureReading {
+        ratio: 0.0,
+        ohms: 0.0,
+        psi,
+        bounds: MinMax::new(psi),
+        state: SenderState::Ok,
+    };
+    let image = snapshot(|d| draw_oil_pressure_gauge(d, oil).unwrap());
+    assert_golden(name, &image);
+}
+
+fn tach(name: &str, rpm: f32) {
+    let image = snapshot(|d| draw_tach_gauge(d, TachReading { rpm }).unwrap());
+    assert_golden(name, &image);
+}
+
+#[test]
+fn fuel_empty() {
+    fuel("fuel_empty", 0);
+}
+
+#[test]
+fn fuel_half() {
+    fuel("fuel_half", 50);
+}
+
+#[test]
+fn fuel_full() {
+    fuel("fuel_full", 100);
+}
+
+#[test]
+fn fuel_low_warning() {
+    fuel_with_warning("fuel_low_warning", 5, SenderState::Ok, Some(Alert::LowFuel));
+}
+
+#[test]
+fn fuel_sender_open() {
+    fuel_with_warning(
+        "fuel_sender_open",
+        0,
+        SenderState::Open,
+        Some(Alert::FuelSender),
+    );
+}
+
+#[test]
+fn temp_cold() {
+    temp("temp_cold", 100.0);
+}
+
+#[test]
+fn temp_normal() {
+    temp("temp_normal", 195.0);
+}
+
+#[test]
+fn temp_hot() {
+    temp("temp_hot", 260.0);
+}
+
+#[test]
+fn temp_sender_shorted() {
+    temp_with_state("temp_sender_shorted", 400.0, SenderState::Short, None);
+}
+
+#[test]
+fn temp_with_range() {
+    let range = MinMax {
+        min: 88.0,
+        max: 203.0,
+    };
+    temp_with_state("temp_with_range", 195.0, SenderState::Ok, Some(range));
+}
+
+#[test]
+fn temp_without_reference() {
+    temp_with_state("temp_no_reference", 150.0, SenderState::NoReference, None);
+}
+
+#[test]
+fn oil_idle() {
+    oil("oil_idle", 15.0);
+}
+
+#[test]
+fn oil_cruise() {
+    oil("oil_cruise", 45.0);
+}
+
+#[test]
+fn tach_stopped() {
+    tach("tach_stopped", 0.0);
+}
+
+#[test]
+fn tach_cruise() {
+    tach("tach_cruise", 2_480.0);
+}
+
+#[test]
+fn speed_cruise() {
+    let odometer = Odometer::new(Distance::from_tenths(1_234_567));
+    let image = snapshot(|d| draw_speed_gauge(d, SpeedReading { mph: 55.4 }, &odometer).unwrap());
+    assert_golden("speed_cruise", &image);
+}
+
+#[test]
+fn trip_meters() {
+    let mut odometer = Odometer::new(Distance::from_tenths(1_234_567));
+    odometer.add_pulses(4_000 * 312, 4_000);
+    odometer.reset_trip(Trip::A);
+    odometer.add_pulses(4_000 * 47 + 1_200, 4_000);
+    let image = snapshot(|d| draw_trip_page(d, &odometer).unwrap());
+    assert_golden("trip_meters", &image);
+}
+
+#[test]
+fn fuel_display_fault() {
+    let coolant = CoolantReading {
+        ohms: 0.0,
+        fahrenheit: 195.0,
+        bounds: MinMax::new(195.0),
+        state: SenderState::Ok,
+    };
+    let image = snapshot(|d| {
+        draw_temp_gauge(d, coolant, None, &GaugeConfig::default()).unwrap();
+        draw_fault(d, Device::Display2).unwrap();
+    });
+    assert_golden("fuel_display_fault", &image);
+}
+
+#[test]
+fn boot_after_watchdog() {
+    let resets = ResetLog {
+        reason: ResetReason::Watchdog,
+        watchdog: 3,
+        software: 1,
+    };
+    let image = snapshot(|d| draw_boot_screen(d, &resets).unwrap());
+    assert_golden("boot_after_watchdog", &image);
+}
+
+#[test]
+fn crash_report() {
+    let text =
+        "panicked at firmware/src/main.rs:236:6:\ncalled `Option::unwrap()` on a `None` value";
+    let image = snapshot(|d| draw_crash_report(d, text).unwrap());
+    assert_golden("crash_report", &image);
+}
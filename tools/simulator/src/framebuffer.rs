@@ -0,0 +1,93 @@
+//! In-memory stand-in for the 128x64 SSD1306 buffer, with PBM/PNG output.
+
+use std::{
+    convert::Infallible,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 64;
+
+pub struct Framebuffer {
+    pixels: [bool; WIDTH * HEIGHT],
+}
+
+impl Framebuffer {
+    pub fn new() -> Self {
+        Self {
+            pixels: [false; WIDTH * HEIGHT],
+        }
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * WIDTH + x]
+    }
+
+    /// Binary PBM (P4), one bit per pixel with 1 = lit.
+    pub fn write_pbm(&self, path: &Path, scale: usize) -> io::Result<()> {
+        let (w, h) = (WIDTH * scale, HEIGHT * scale);
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "P4\n{} {}\n", w, h)?;
+        for y in 0..h {
+            let mut row = vec![0u8; w.div_ceil(8)];
+            for x in 0..w {
+                if self.pixel(x / scale, y / scale) {
+                    row[x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+            out.write_all(&row)?;
+        }
+        out.flush()
+    }
+
+    /// Greyscale PNG, lit pixels drawn white on black like the OLED.
+    pub fn write_png(&self, path: &Path, scale: usize) -> io::Result<()> {
+        let (w, h) = (WIDTH * scale, HEIGHT * scale);
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(File::create(path)?), w as u32, h as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+
+        let mut data = Vec::with_capacity(w * h);
+        for y in 0..h {
+            for x in 0..w {
+                data.push(if self.pixel(x / scale, y / scale) {
+                    0xff
+                } else {
+                    0x00
+                });
+            }
+        }
+        writer.write_image_data(&data).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+}
+
+impl OriginDimensions for Framebuffer {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for Framebuffer {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(p, color) in pixels {
+            // Same as the SSD1306 driver: anything off-panel is dropped.
+            if (0..WIDTH as i32).contains(&p.x) && (0..HEIGHT as i32).contains(&p.y) {
+                self.pixels[p.y as usize * WIDTH + p.x as usize] = color.is_on();
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+//! Renders the cluster screens for a sweep of inputs into image files.
+//!
+//! ```text
+//! cargo run -p hardbody-sim -- [--out DIR] [--scale N] [--format png|pbm|both]
+//! ```
+//!
+//! Every frame goes through the same `hardbody-core` draw functions as the
+//! firmware, into a 128x64 framebuffer, so the files match the panels
+//! pixel-for-pixel (before `--scale`).
+
+mod framebuffer;
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+use hardbody_core::{
+    draw_page, draw_warning, Alert, BatteryReading, CoolantReading, Distance, FuelReading,
+    GaugeConfig, MinMax, Odometer, OilPressureReading, Page, RailState, ReferenceReading,
+    SenderState, Snapshot, SpeedReading, TachReading, Trip,
+};
+
+use framebuffer::Framebuffer;
+
+/// One rendered image: file stem and the drawing that produces it.
+struct Frame {
+    name: String,
+    draw: Box<dyn Fn(&mut Framebuffer)>,
+}
+
+/// Readings every sweep starts from; each sweep varies one value.
+const BASELINE: Snapshot = Snapshot {
+    coolant: CoolantReading {
+        ohms: 0.0,
+        fahrenheit: 195.0,
+        bounds: MinMax::new(195.0),
+        state: SenderState::Ok,
+    },
+    coolant_range: None,
+    reference: ReferenceReading {
+        volts: 3.30,
+        state: RailState::Ok,
+    },
+    fuel: FuelReading {
+        ratio: 0.0,
+        ohms: 0.0,
+        percent: 60,
+        bounds: MinMax::new(60.0),
+        state: SenderState::Ok,
+    },
+    battery: BatteryReading { volts: 12.6 },
+    oil: OilPressureReading {
+        ratio: 0.0,
+        ohms: 0.0,
+        psi: 40.0,
+        bounds: MinMax::new(40.0),
+        state: SenderState::Ok,
+    },
+    tach: TachReading { rpm: 800.0 },
+    speed: SpeedReading { mph: 0.0 },
+    odometer: Odometer::new(Distance::from_tenths(1_234_567)),
+};
+
+fn page_frame(name: String, page: Page, snapshot: Snapshot, alert: Option<Alert>) -> Frame {
+    Frame {
+        name,
+        draw: Box::new(move |fb| {
+            draw_page(fb, page, &snapshot, &GaugeConfig::default()).unwrap();
+            if let Some(alert) = alert {
+                draw_warning(fb, alert).unwrap();
+            }
+        }),
+    }
+}
+
+fn fuel_frames() -> impl Iterator<Item = Frame> {
+    (0..=100).step_by(10).map(|percent| {
+        let mut snapshot = BASELINE;
+        snapshot.fuel.percent = percent;
+        page_frame(format!("fuel_{:03}", percent), Page::Fuel, snapshot, None)
+    })
+}
+
+fn temp_frames() -> impl Iterator<Item = Frame> {
+    (100..=280).step_by(10).map(|f| {
+        let mut snapshot = BASELINE;
+        snapshot.coolant.fahrenheit = f as f32;
+        // Warming up from a cold start
+        snapshot.coolant_range = Some(MinMax {
+            min: 100.0,
+            max: f as f32,
+        });
+        page_frame(format!("temp_{:03}", f), Page::Coolant, snapshot, None)
+    })
+}
+
+fn oil_frames() -> impl Iterator<Item = Frame> {
+    (0..=80).step_by(10).map(|psi| {
+        let mut snapshot = BASELINE;
+        snapshot.oil.psi = psi as f32;
+        page_frame(format!("oil_{:03}", psi), Page::OilPressure, snapshot, None)
+    })
+}
+
+fn tach_frames() -> impl Iterator<Item = Frame> {
+    (0..=6_500).step_by(500).map(|rpm| {
+        let mut snapshot = BASELINE;
+        snapshot.tach.rpm = rpm as f32;
+        page_frame(format!("tach_{:04}", rpm), Page::Tach, snapshot, None)
+    })
+}
+
+fn speed_frames() -> impl Iterator<Item = Frame> {
+    (0..=110).step_by(10).map(|mph| {
+        let mut snapshot = BASELINE;
+        snapshot.speed.mph = mph as f32;
+        page_frame(format!("speed_{:03}", mph), Page::Speed, snapshot, None)
+    })
+}
+
+/// The trip page after a drive, with trip A reset part way.
+fn trip_frames() -> impl Iterator<Item = Frame> {
+    let mut snapshot = BASELINE;
+    snapshot.odometer.add_pulses(4_000 * 312, 4_000);
+    snapshot.odometer.reset_trip(Trip::A);
+    snapshot.odometer.add_pulses(4_000 * 47 + 1_200, 4_000);
+    std::iter::once(page_frame("trip".into(), Page::Trip, snapshot, None))
+}
+
+/// Each alert over its page with every value in the red.
+fn warning_frames() -> impl Iterator<Item = Frame> {
+    let mut alarming = BASELINE;
+    alarming.coolant.fahrenheit = 240.0;
+    alarming.fuel.percent = 5;
+    alarming.battery.volts = 11.4;
+    alarming.oil.psi = 3.0;
+
+    Alert::ALL.into_iter().map(move |alert| {
+        page_frame(
+            format!(
+                "warning_{}",
+                alert.message().replace(' ', "_").to_lowercase()
+            ),
+            alert.page(),
+            alarming,
+            Some(alert),
+        )
+    })
+}
+
+/// Every screen the simulator knows how to sweep.
+fn frames() -> impl Iterator<Item = Frame> {
+    fuel_frames()
+        .chain(temp_frames())
+        .chain(oil_frames())
+        .chain(tach_frames())
+        .chain(speed_frames())
+        .chain(trip_frames())
+        .chain(warning_frames())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Png,
+    Pbm,
+    Both,
+}
+
+struct Options {
+    out: PathBuf,
+    scale: usize,
+    format: Format,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: hardbody-sim [--out DIR] [--scale N] [--format png|pbm|both]");
+    process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut opts = Options {
+        out: PathBuf::from("frames"),
+        scale: 4,
+        format: Format::Png,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--out" => opts.out = PathBuf::from(value()),
+            "--scale" => {
+                opts.scale = match value().parse() {
+                    Ok(n) if n > 0 => n,
+                    _ => usage(),
+                }
+            }
+            "--format" => {
+                opts.format = match value().as_str() {
+                    "png" => Format::Png,
+                    "pbm" => Format::Pbm,
+                    "both" => Format::Both,
+                    _ => usage(),
+                }
+            }
+            _ => usage(),
+        }
+    }
+    opts
+}
+
+fn write_frame(fb: &Framebuffer, stem: &Path, opts: &Options) -> io::Result<()> {
+    if opts.format != Format::Pbm {
+        fb.write_png(&stem.with_extension("png"), opts.scale)?;
+    }
+    if opts.format != Format::Png {
+        fb.write_pbm(&stem.with_extension("pbm"), opts.scale)?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let opts = parse_args();
+    if let Err(e) = fs::create_dir_all(&opts.out) {
+        eprintln!("{}: {}", opts.out.display(), e);
+        process::exit(1);
+    }
+
+    let mut count = 0;
+    for frame in frames() {
+        let mut fb = Framebuffer::new();
+        fb.clear(BinaryColor::Off).unwrap();
+        (frame.draw)(&mut fb);
+
+        if let Err(e) = write_frame(&fb, &opts.out.join(&frame.name), &opts) {
+            eprintln!("{}: {}", frame.name, e);
+            process::exit(1);
+        }
+        count += 1;
+    }
+    println!("wrote {} frames to {}", count, opts.out.display());
+}